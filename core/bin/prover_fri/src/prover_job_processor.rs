@@ -26,6 +26,7 @@ use zksync_config::configs::FriProverConfig;
 use zksync_dal::ConnectionPool;
 use zksync_object_store::{FriCircuitKey, ObjectStore};
 use zksync_queued_job_processor::{async_trait, JobProcessor};
+use zksync_types::proofs::AggregationRound;
 use zksync_types::L1BatchNumber;
 
 use zksync_config::configs::fri_prover_group::CircuitIdRoundTuple;
@@ -394,6 +395,24 @@ impl JobProcessor for Prover {
                 )
                 .await;
         }
+        if job_metadata.aggregation_round == AggregationRound::BasicCircuits {
+            let leaf_job_id = transaction
+                .fri_witness_generator_dal()
+                .mark_leaf_aggregation_proof_available(
+                    job_metadata.block_number,
+                    job_metadata.circuit_id,
+                    job_metadata.sequence_number,
+                )
+                .await;
+            if leaf_job_id.is_none() {
+                vlog::warn!(
+                    "Proof of prover job {job_id} isn't tracked in the availability bitmap of \
+                     leaf aggregation job for block {} and circuit {}",
+                    job_metadata.block_number.0,
+                    job_metadata.circuit_id
+                );
+            }
+        }
         transaction.commit().await;
     }
 }
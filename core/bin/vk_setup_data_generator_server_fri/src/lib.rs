@@ -1,6 +1,8 @@
 #![feature(generic_const_exprs)]
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
+use std::sync::RwLock;
 
 use circuit_definitions::boojum::cs::implementations::hints::{
     DenseVariablesCopyHint, DenseWitnessCopyHint,
@@ -20,6 +22,7 @@ use circuit_definitions::circuit_definitions::base_layer::ZkSyncBaseLayerVerific
 use circuit_definitions::circuit_definitions::recursion_layer::{
     ZkSyncRecursionLayerStorageType, ZkSyncRecursionLayerVerificationKey,
 };
+use once_cell::sync::Lazy;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use zksync_config::configs::FriProverConfig;
@@ -87,8 +90,8 @@ pub fn get_base_vk_path() -> String {
     )
 }
 
-fn get_file_path(key: ProverServiceDataKey, service_data_type: ProverServiceDataType) -> String {
-    let name = match key.round {
+fn get_data_name(key: &ProverServiceDataKey) -> String {
+    match key.round {
         AggregationRound::BasicCircuits => {
             format!("basic_{}", key.circuit_id)
         }
@@ -97,52 +100,148 @@ fn get_file_path(key: ProverServiceDataKey, service_data_type: ProverServiceData
         }
         AggregationRound::NodeAggregation => "node".to_string(),
         AggregationRound::Scheduler => "scheduler".to_string(),
-    };
+    }
+}
+
+fn get_vk_file_path(vk_base_path: &str, key: &ProverServiceDataKey) -> String {
+    format!(
+        "{}/verification_{}_key.json",
+        vk_base_path,
+        get_data_name(key)
+    )
+}
+
+fn get_file_path(key: ProverServiceDataKey, service_data_type: ProverServiceDataType) -> String {
     match service_data_type {
-        ProverServiceDataType::VerificationKey => {
-            format!("{}/verification_{}_key.json", get_base_vk_path(), name)
-        }
+        ProverServiceDataType::VerificationKey => get_vk_file_path(&get_base_vk_path(), &key),
         ProverServiceDataType::SetupData => {
             format!(
                 "{}/setup_{}_data.bin",
                 FriProverConfig::from_env().setup_data_path,
-                name
+                get_data_name(&key)
             )
         }
     }
 }
 
+/// Memoized verification keys of a single circuit layer, keyed by the circuit type.
+/// Verification keys are loaded at most once per process, unless the cache is cleared
+/// (e.g., after the setup data was updated on a shared volume).
+struct VkCache<V> {
+    vks: RwLock<HashMap<u8, V>>,
+}
+
+impl<V: Clone> VkCache<V> {
+    fn new() -> Self {
+        Self {
+            vks: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn get_or_load(&self, circuit_type: u8, load: impl FnOnce(u8) -> V) -> V {
+        if let Some(vk) = self.vks.read().unwrap().get(&circuit_type) {
+            return vk.clone();
+        }
+        // The lock isn't held while loading; concurrent loads of the same VK are benign.
+        let vk = load(circuit_type);
+        self.vks
+            .write()
+            .unwrap()
+            .entry(circuit_type)
+            .or_insert(vk)
+            .clone()
+    }
+
+    /// Returns the sorted circuit types of the loaded VKs.
+    fn circuit_types(&self) -> Vec<u8> {
+        let mut circuit_types: Vec<_> = self.vks.read().unwrap().keys().copied().collect();
+        circuit_types.sort_unstable();
+        circuit_types
+    }
+
+    fn clear(&self) {
+        self.vks.write().unwrap().clear();
+    }
+}
+
+/// Circuit types of the verification keys currently held in memory, per circuit layer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VkCacheStats {
+    pub base_layer_circuit_types: Vec<u8>,
+    pub recursive_layer_circuit_types: Vec<u8>,
+}
+
+static BASE_LAYER_VKS: Lazy<VkCache<ZkSyncBaseLayerVerificationKey>> = Lazy::new(VkCache::new);
+static RECURSIVE_LAYER_VKS: Lazy<VkCache<ZkSyncRecursionLayerVerificationKey>> =
+    Lazy::new(VkCache::new);
+
+pub fn vk_cache_stats() -> VkCacheStats {
+    VkCacheStats {
+        base_layer_circuit_types: BASE_LAYER_VKS.circuit_types(),
+        recursive_layer_circuit_types: RECURSIVE_LAYER_VKS.circuit_types(),
+    }
+}
+
+/// Evicts all memoized verification keys, so that they're reloaded from disk on the next access.
+/// Leaf params are computed from these keys, so they're refreshed as well.
+pub fn clear_vk_caches() {
+    BASE_LAYER_VKS.clear();
+    RECURSIVE_LAYER_VKS.clear();
+}
+
 pub fn get_base_layer_vk_for_circuit_type(circuit_type: u8) -> ZkSyncBaseLayerVerificationKey {
-    let filepath = get_file_path(
-        ProverServiceDataKey::new(circuit_type, AggregationRound::BasicCircuits),
-        ProverServiceDataType::VerificationKey,
+    BASE_LAYER_VKS.get_or_load(circuit_type, |circuit_type| {
+        load_base_layer_vk(&get_base_vk_path(), circuit_type)
+    })
+}
+
+pub fn get_recursive_layer_vk_for_circuit_type(
+    circuit_type: u8,
+) -> ZkSyncRecursionLayerVerificationKey {
+    RECURSIVE_LAYER_VKS.get_or_load(circuit_type, |circuit_type| {
+        load_recursive_layer_vk(&get_base_vk_path(), circuit_type)
+    })
+}
+
+fn load_base_layer_vk(vk_base_path: &str, circuit_type: u8) -> ZkSyncBaseLayerVerificationKey {
+    let filepath = get_vk_file_path(
+        vk_base_path,
+        &ProverServiceDataKey::new(circuit_type, AggregationRound::BasicCircuits),
     );
     vlog::info!("Fetching verification key from path: {}", filepath);
-    let text = std::fs::read_to_string(&filepath)
-        .unwrap_or_else(|_| panic!("Failed reading verification key from path: {}", filepath));
+    let text = read_vk_file("base layer", circuit_type, &filepath);
     serde_json::from_str::<ZkSyncBaseLayerVerificationKey>(&text).unwrap_or_else(|_| {
         panic!(
-            "Failed deserializing verification key from path: {}",
-            filepath
+            "Failed deserializing base layer verification key for circuit type {} from path: {}",
+            circuit_type, filepath
         )
     })
 }
 
-pub fn get_recursive_layer_vk_for_circuit_type(
+fn load_recursive_layer_vk(
+    vk_base_path: &str,
     circuit_type: u8,
 ) -> ZkSyncRecursionLayerVerificationKey {
     let round = get_round_for_recursive_circuit_type(circuit_type);
-    let filepath = get_file_path(
-        ProverServiceDataKey::new(circuit_type, round),
-        ProverServiceDataType::VerificationKey,
+    let filepath = get_vk_file_path(
+        vk_base_path,
+        &ProverServiceDataKey::new(circuit_type, round),
     );
     vlog::info!("Fetching verification key from path: {}", filepath);
-    let text = std::fs::read_to_string(&filepath)
-        .unwrap_or_else(|_| panic!("Failed reading verification key from path: {}", filepath));
+    let text = read_vk_file("recursive layer", circuit_type, &filepath);
     serde_json::from_str::<ZkSyncRecursionLayerVerificationKey>(&text).unwrap_or_else(|_| {
         panic!(
-            "Failed deserializing verification key from path: {}",
-            filepath
+            "Failed deserializing recursive layer verification key for circuit type {} from path: {}",
+            circuit_type, filepath
+        )
+    })
+}
+
+fn read_vk_file(vk_kind: &str, circuit_type: u8, filepath: &str) -> String {
+    std::fs::read_to_string(filepath).unwrap_or_else(|_| {
+        panic!(
+            "Failed reading {} verification key for circuit type {} from path: {}",
+            vk_kind, circuit_type, filepath
         )
     })
 }
@@ -214,3 +313,68 @@ pub fn save_setup_data(key: ProverServiceDataKey, serialized_setup_data: &Vec<u8
     std::fs::write(filepath.clone(), serialized_setup_data)
         .unwrap_or_else(|_| panic!("Failed saving setup-data at path: {:?}", filepath));
 }
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, UnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn vks_are_loaded_once() {
+        let cache = VkCache::new();
+        let loads = AtomicUsize::new(0);
+        let load = |circuit_type: u8| {
+            loads.fetch_add(1, Ordering::SeqCst);
+            u32::from(circuit_type) * 10
+        };
+        assert_eq!(cache.get_or_load(3, load), 30);
+        assert_eq!(cache.get_or_load(3, load), 30);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get_or_load(4, load), 40);
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cleared_cache_reloads_vks() {
+        let cache = VkCache::new();
+        let loads = AtomicUsize::new(0);
+        let load = |circuit_type: u8| {
+            loads.fetch_add(1, Ordering::SeqCst);
+            u32::from(circuit_type) * 10
+        };
+        cache.get_or_load(4, load);
+        cache.get_or_load(3, load);
+        assert_eq!(cache.circuit_types(), [3, 4]);
+
+        cache.clear();
+        assert!(cache.circuit_types().is_empty());
+        assert_eq!(cache.get_or_load(3, load), 30);
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.circuit_types(), [3]);
+    }
+
+    fn panic_message(f: impl FnOnce() + UnwindSafe) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        *payload.downcast::<String>().unwrap()
+    }
+
+    #[test]
+    fn missing_vk_error_identifies_the_vk() {
+        let message = panic_message(|| {
+            load_base_layer_vk("/non-existent", 3);
+        });
+        assert!(
+            message.contains("base layer verification key for circuit type 3"),
+            "{message}"
+        );
+        let message = panic_message(|| {
+            load_recursive_layer_vk("/non-existent", 5);
+        });
+        assert!(
+            message.contains("recursive layer verification key for circuit type 5"),
+            "{message}"
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use std::{
     collections::{hash_map::DefaultHasher, HashMap, HashSet},
     hash::Hasher,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
@@ -32,7 +32,7 @@ use zksync_queued_job_processor::JobProcessor;
 use zksync_state::{PostgresStorage, StorageView};
 use zksync_types::proofs::AggregationRound;
 use zksync_types::{
-    proofs::{BasicCircuitWitnessGeneratorInput, PrepareBasicCircuitsJob},
+    proofs::{BaseLayerCircuitId, BasicCircuitWitnessGeneratorInput, PrepareBasicCircuitsJob},
     Address, L1BatchNumber, BOOTLOADER_ADDRESS, H256, U256,
 };
 use zksync_utils::{bytes_to_chunks, h256_to_u256, u256_to_h256};
@@ -89,7 +89,10 @@ impl BasicWitnessGenerator {
     ) -> Self {
         Self {
             config: Arc::new(config),
-            object_store: store_factory.create_store().await.into(),
+            object_store: store_factory
+                .create_store_for_round(AggregationRound::BasicCircuits)
+                .await
+                .into(),
             public_blob_store,
             connection_pool,
             prover_connection_pool,
@@ -162,6 +165,9 @@ impl JobProcessor for BasicWitnessGenerator {
     const SERVICE_NAME: &'static str = "fri_basic_circuit_witness_generator";
 
     async fn get_next_job(&self) -> Option<(Self::JobId, Self::Job)> {
+        if !self.config.worker_settings().picks_jobs() {
+            return None;
+        }
         let mut prover_connection = self.prover_connection_pool.access_storage().await;
         let last_l1_batch_to_process = self.config.last_l1_batch_to_process();
 
@@ -241,6 +247,10 @@ impl JobProcessor for BasicWitnessGenerator {
             }
         }
     }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        Some(self.config.witness_generation_timeout())
+    }
 }
 
 async fn process_basic_circuits_job(
@@ -303,7 +313,9 @@ async fn update_database(
             block_number,
             &blob_urls.closed_form_inputs_and_urls,
             &blob_urls.scheduler_witness_url,
-            get_recursive_layer_circuit_id_for_base_layer,
+            |circuit_id| {
+                get_recursive_layer_circuit_id_for_base_layer(BaseLayerCircuitId(circuit_id)).0
+            },
         )
         .await;
     prover_connection
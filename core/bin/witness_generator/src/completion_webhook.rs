@@ -0,0 +1,169 @@
+//! Opt-in HTTP webhook notifying integrations about batches whose aggregation is complete.
+
+use serde::Serialize;
+
+use std::time::Duration;
+
+use zksync_types::L1BatchNumber;
+
+const MAX_ATTEMPTS: u32 = 5;
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Outcome of the scheduler witness generation for a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchCompletionStatus {
+    Successful,
+    Failed,
+}
+
+/// JSON payload posted to the webhook.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchCompletionPayload {
+    pub l1_batch_number: u32,
+    pub status: BatchCompletionStatus,
+    pub time_taken_ms: u64,
+}
+
+impl BatchCompletionPayload {
+    pub fn new(
+        l1_batch_number: L1BatchNumber,
+        status: BatchCompletionStatus,
+        time_taken: Duration,
+    ) -> Self {
+        Self {
+            l1_batch_number: l1_batch_number.0,
+            status,
+            time_taken_ms: time_taken.as_millis() as u64,
+        }
+    }
+}
+
+/// Posts [`BatchCompletionPayload`]s to the configured URL. Delivery is best-effort: failed requests
+/// are retried with exponential backoff, after which the payload is dropped with a warning.
+#[derive(Debug, Clone)]
+pub struct CompletionWebhook {
+    client: reqwest::Client,
+    url: String,
+    auth_header: Option<String>,
+    notify_failures: bool,
+    base_backoff: Duration,
+}
+
+impl CompletionWebhook {
+    pub fn new(url: String, auth_header: Option<String>, notify_failures: bool) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            auth_header,
+            notify_failures,
+            base_backoff: BASE_BACKOFF,
+        }
+    }
+
+    /// Sends the payload in the background, so that a slow endpoint doesn't hold up the job.
+    pub fn notify(&self, payload: BatchCompletionPayload) {
+        if payload.status == BatchCompletionStatus::Failed && !self.notify_failures {
+            return;
+        }
+        let webhook = self.clone();
+        tokio::spawn(async move { webhook.deliver(&payload).await });
+    }
+
+    async fn deliver(&self, payload: &BatchCompletionPayload) {
+        let mut backoff = self.base_backoff;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.post(payload).await {
+                Ok(()) => return,
+                Err(err) => vlog::warn!(
+                    "Failed delivering completion webhook for batch {} ({attempt}/{MAX_ATTEMPTS}): {err}",
+                    payload.l1_batch_number
+                ),
+            }
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+
+    async fn post(&self, payload: &BatchCompletionPayload) -> reqwest::Result<()> {
+        let mut request = self.client.post(&self.url).json(payload);
+        if let Some(auth_header) = &self.auth_header {
+            request = request.header(reqwest::header::AUTHORIZATION, auth_header);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accepts a single HTTP request, responding with `status_line` and returning the raw request.
+    async fn serve_once(listener: &TcpListener, status_line: &str) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = vec![];
+        let mut buffer = [0_u8; 1024];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+            let request = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = request.split_once("\r\n\r\n") {
+                let content_length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |len| len.parse().unwrap());
+                if body.len() >= content_length {
+                    break;
+                }
+            }
+        }
+        let response = format!("{status_line}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+        stream.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    #[tokio::test]
+    async fn payload_is_delivered_after_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let webhook = CompletionWebhook {
+            base_backoff: Duration::from_millis(1),
+            ..CompletionWebhook::new(
+                format!("http://{}/batches", listener.local_addr().unwrap()),
+                Some("Bearer secret".to_owned()),
+                false,
+            )
+        };
+        let payload = BatchCompletionPayload::new(
+            L1BatchNumber(42),
+            BatchCompletionStatus::Successful,
+            Duration::from_millis(1500),
+        );
+
+        let server = async {
+            serve_once(&listener, "HTTP/1.1 503 Service Unavailable").await;
+            serve_once(&listener, "HTTP/1.1 200 OK").await
+        };
+        let ((), request) = tokio::join!(webhook.deliver(&payload), server);
+
+        assert!(request.starts_with("POST /batches "));
+        assert!(request.contains("authorization: Bearer secret\r\n"));
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "l1_batch_number": 42,
+                "status": "successful",
+                "time_taken_ms": 1500
+            })
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Errors that can occur while generating witnesses.
+
+use std::{error, fmt, ops::RangeInclusive};
+
+use zksync_object_store::ObjectStoreError;
+use zksync_types::proofs::BaseLayerCircuitId;
+
+/// Errors during witness generation that are specific to a single job; they should fail the job
+/// rather than the whole witness generator.
+#[derive(Debug)]
+pub enum WitnessGeneratorError {
+    /// The number of recursion requests aggregated by the job differs from the number of input proofs.
+    AggregationCountMismatch { expected: usize, got: usize },
+    /// The serialized size of the produced artifacts is outside of the range expected for the circuit.
+    ArtifactSizeAnomaly {
+        circuit_id: BaseLayerCircuitId,
+        size: u64,
+        expected: RangeInclusive<u64>,
+    },
+    /// The job has closed form inputs, but no basic circuit proofs to aggregate them with.
+    NoProofsForNonEmptyJob { closed_form_inputs_count: usize },
+    /// A produced recursion queue declares a different number of requests than it holds.
+    InvalidRecursionQueue {
+        index: usize,
+        num_items: u32,
+        witness_len: usize,
+    },
+    /// A blob needed by the job is missing from the object store (e.g., because of replication lag).
+    ArtifactMissing { key: String },
+    /// A blob needed by the job couldn't be fetched from the object store for a reason other than
+    /// it missing (e.g., an access or deserialization error).
+    ArtifactFetchFailed { key: String, err: ObjectStoreError },
+    /// The job has no proofs to aggregate (e.g., because of a malformed queue entry).
+    NoProofs { job_id: u32 },
+    /// A base proof of the job doesn't verify against the verification key of its circuit.
+    ProofVerificationFailed { job_id: u32, index: usize },
+    /// A prover job id is listed more than once among the proofs of the job.
+    DuplicateProofJobId { id: u32 },
+    /// A saved artifact of the job doesn't read back as what the job produced.
+    ArtifactVerificationFailed {
+        job_id: u32,
+        key: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for WitnessGeneratorError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AggregationCountMismatch { expected, got } => write!(
+                formatter,
+                "aggregation count mismatch: expected {expected} aggregated proofs, got {got}"
+            ),
+            Self::ArtifactSizeAnomaly {
+                circuit_id,
+                size,
+                expected,
+            } => write!(
+                formatter,
+                "artifact size anomaly for circuit {circuit_id}: {size} bytes is outside of the expected range {expected:?}"
+            ),
+            Self::NoProofsForNonEmptyJob {
+                closed_form_inputs_count,
+            } => write!(
+                formatter,
+                "no proofs for a job with {closed_form_inputs_count} closed form inputs"
+            ),
+            Self::InvalidRecursionQueue {
+                index,
+                num_items,
+                witness_len,
+            } => write!(
+                formatter,
+                "invalid recursion queue {index}: declares {num_items} requests, but holds {witness_len}"
+            ),
+            Self::ArtifactMissing { key } => {
+                write!(formatter, "leaf aggregation job artifacts missing: {key}")
+            }
+            Self::ArtifactFetchFailed { key, err } => write!(
+                formatter,
+                "failed fetching leaf aggregation job artifact {key}: {err}"
+            ),
+            Self::NoProofs { job_id } => {
+                write!(formatter, "leaf aggregation job {job_id} has no proofs")
+            }
+            Self::ProofVerificationFailed { job_id, index } => write!(
+                formatter,
+                "base proof {index} of job {job_id} failed verification against the circuit VK"
+            ),
+            Self::DuplicateProofJobId { id } => {
+                write!(formatter, "prover job {id} is listed more than once among the proofs")
+            }
+            Self::ArtifactVerificationFailed {
+                job_id,
+                key,
+                reason,
+            } => write!(
+                formatter,
+                "saved artifact {key} of job {job_id} failed verification: {reason}"
+            ),
+        }
+    }
+}
+
+impl WitnessGeneratorError {
+    /// Classifies an error fetching the blob with the `key`: only missing blobs are reported
+    /// as [`Self::ArtifactMissing`], so that other failures aren't mistaken for replication lag.
+    pub fn artifact_fetch(key: String, err: ObjectStoreError) -> Self {
+        match err {
+            ObjectStoreError::KeyNotFound(_) => Self::ArtifactMissing { key },
+            err => Self::ArtifactFetchFailed { key, err },
+        }
+    }
+}
+
+impl error::Error for WitnessGeneratorError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::ArtifactFetchFailed { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
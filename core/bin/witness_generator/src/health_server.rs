@@ -0,0 +1,193 @@
+//! HTTP server exposing the readiness of the witness generator and its metrics, so that it can be
+//! used as a Kubernetes readiness probe.
+
+use async_trait::async_trait;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus_exporter::PrometheusHandle;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use zksync_dal::ConnectionPool;
+use zksync_health_check::{CheckHealth, CheckHealthStatus};
+use zksync_object_store::{Bucket, ObjectStore};
+
+/// Max time a single health check may take before the generator is considered not ready.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const SMOKE_CHECK_KEY: &str = "witness_generator_health_check.bin";
+
+/// Checks that a connection can be acquired from the pool.
+#[derive(Debug)]
+pub struct DatabaseHealthCheck {
+    connection_pool: ConnectionPool,
+}
+
+impl DatabaseHealthCheck {
+    pub fn new(connection_pool: ConnectionPool) -> Self {
+        Self { connection_pool }
+    }
+}
+
+#[async_trait]
+impl CheckHealth for DatabaseHealthCheck {
+    async fn check_health(&self) -> CheckHealthStatus {
+        let connection_pool = self.connection_pool.clone();
+        // `access_storage()` panics once it runs out of retries, so it's isolated in a task.
+        let acquire = tokio::spawn(async move {
+            connection_pool.access_storage().await;
+        });
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, acquire).await {
+            Ok(Ok(())) => CheckHealthStatus::Ready,
+            Ok(Err(_)) => CheckHealthStatus::NotReady("failed connecting to prover DB".to_owned()),
+            Err(_) => CheckHealthStatus::NotReady("timed out connecting to prover DB".to_owned()),
+        }
+    }
+}
+
+/// Checks that a blob can be written to and read back from the object store.
+#[derive(Debug)]
+pub struct ObjectStoreHealthCheck {
+    object_store: Box<dyn ObjectStore>,
+}
+
+impl ObjectStoreHealthCheck {
+    pub fn new(object_store: Box<dyn ObjectStore>) -> Self {
+        Self { object_store }
+    }
+
+    async fn smoke_check(&self) -> Result<(), String> {
+        let value = b"ok".to_vec();
+        self.object_store
+            .put_raw(Bucket::ProverJobsFri, SMOKE_CHECK_KEY, value.clone())
+            .await
+            .map_err(|err| format!("failed writing to object store: {err}"))?;
+        let read_value = self
+            .object_store
+            .get_raw(Bucket::ProverJobsFri, SMOKE_CHECK_KEY)
+            .await
+            .map_err(|err| format!("failed reading from object store: {err}"))?;
+        if read_value != value {
+            return Err("object store returned a different blob than written".to_owned());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CheckHealth for ObjectStoreHealthCheck {
+    async fn check_health(&self) -> CheckHealthStatus {
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, self.smoke_check()).await {
+            Ok(Ok(())) => CheckHealthStatus::Ready,
+            Ok(Err(message)) => CheckHealthStatus::NotReady(message),
+            Err(_) => CheckHealthStatus::NotReady("timed out accessing object store".to_owned()),
+        }
+    }
+}
+
+/// Runs the health checks in order, returning the message of the first failing one.
+async fn health_status(healthchecks: &[Box<dyn CheckHealth>]) -> (StatusCode, String) {
+    for healthcheck in healthchecks {
+        if let CheckHealthStatus::NotReady(message) = healthcheck.check_health().await {
+            return (StatusCode::SERVICE_UNAVAILABLE, message);
+        }
+    }
+    (StatusCode::OK, "Everything is working fine".to_owned())
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    healthchecks: &[Box<dyn CheckHealth>],
+    metrics: &PrometheusHandle,
+) -> Response<Body> {
+    let (status, body) = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/health") => health_status(healthchecks).await,
+        (&Method::GET, "/metrics") => (StatusCode::OK, metrics.render()),
+        _ => (StatusCode::NOT_FOUND, "Not found".to_owned()),
+    };
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+/// Serves `/health` and `/metrics` on `bind_address` until `stop_receiver` is signaled.
+pub fn run_health_server(
+    bind_address: SocketAddr,
+    healthchecks: Vec<Box<dyn CheckHealth>>,
+    metrics: PrometheusHandle,
+    mut stop_receiver: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    let state = Arc::new((healthchecks, metrics));
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = state.clone();
+                async move {
+                    let (healthchecks, metrics) = &*state;
+                    Ok::<_, Infallible>(handle_request(request, healthchecks, metrics).await)
+                }
+            }))
+        }
+    });
+    let server = Server::bind(&bind_address)
+        .serve(make_service)
+        .with_graceful_shutdown(async move {
+            stop_receiver.changed().await.ok();
+        });
+    vlog::info!("Starting witness generator health server on {bind_address}");
+    tokio::spawn(async move {
+        server
+            .await
+            .expect("witness generator health server crashed");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zksync_object_store::{ObjectStoreError, ObjectStoreFactory};
+
+    #[derive(Debug)]
+    struct FailingStore;
+
+    #[async_trait]
+    impl ObjectStore for FailingStore {
+        async fn get_raw(&self, _bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+            Err(ObjectStoreError::KeyNotFound(key.into()))
+        }
+
+        async fn put_raw(
+            &self,
+            _bucket: Bucket,
+            _key: &str,
+            _value: Vec<u8>,
+        ) -> Result<(), ObjectStoreError> {
+            Err(ObjectStoreError::Other("bucket is unavailable".into()))
+        }
+
+        async fn remove_raw(&self, _bucket: Bucket, _key: &str) -> Result<(), ObjectStoreError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn degraded_object_store_fails_health_check() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let healthchecks: Vec<Box<dyn CheckHealth>> =
+            vec![Box::new(ObjectStoreHealthCheck::new(object_store))];
+        let (status, _) = health_status(&healthchecks).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let healthchecks: Vec<Box<dyn CheckHealth>> = vec![Box::new(ObjectStoreHealthCheck::new(
+            Box::new(FailingStore),
+        ))];
+        let (status, message) = health_status(&healthchecks).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(message.contains("bucket is unavailable"), "{message}");
+    }
+}
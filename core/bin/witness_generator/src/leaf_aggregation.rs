@@ -2,65 +2,145 @@ use zkevm_test_harness::witness::recursive_aggregation::{
     compute_leaf_params, create_leaf_witnesses,
 };
 
-use std::time::Instant;
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use circuit_definitions::aux_definitions::witness_oracle::VmWitnessOracle;
+use circuit_definitions::boojum::cs::implementations::pow::NoPow;
 use circuit_definitions::circuit_definitions::base_layer::{
-    ZkSyncBaseLayerClosedFormInput, ZkSyncBaseLayerProof, ZkSyncBaseLayerVerificationKey,
+    ZkSyncBaseLayerCircuit, ZkSyncBaseLayerClosedFormInput, ZkSyncBaseLayerProof,
+    ZkSyncBaseLayerVerificationKey,
 };
 use circuit_definitions::circuit_definitions::recursion_layer::ZkSyncRecursiveLayerCircuit;
 use circuit_definitions::encodings::recursion_request::RecursionQueueSimulator;
+use circuit_definitions::ZkSyncDefaultRoundFunction;
+use futures::FutureExt;
+use tracing::{Instrument, Span};
 use zkevm_test_harness::boojum::field::goldilocks::GoldilocksField;
+use zkevm_test_harness::prover_utils::verify_base_layer_proof;
 use zksync_vk_setup_data_server_fri::{
     get_base_layer_vk_for_circuit_type, get_recursive_layer_vk_for_circuit_type,
 };
 
+use crate::error::WitnessGeneratorError;
+use crate::metrics_sink::MetricsSink;
+use crate::resource_usage::{
+    peak_memory_bytes, resident_memory_bytes, JobResourceUsage, ResourceUsageCsvWriter,
+};
 use crate::utils::{
     get_recursive_layer_circuit_id_for_base_layer, load_proofs_for_job_ids,
-    save_node_aggregations_artifacts, save_recursive_layer_prover_input_artifacts,
-    ClosedFormInputWrapper, FriProofWrapper,
+    save_node_aggregations_artifacts, save_recursive_layer_prover_input_artifacts, time_in_queue,
+    AggregationWrapper, CircuitWrapper, ClosedFormInputWrapper, FriProofWrapper, LoadProofsError,
 };
 use zkevm_test_harness::zkevm_circuits::recursion::leaf_layer::input::RecursionLeafParametersWitness;
+use zksync_config::configs::object_store::ContentHashAlgorithm;
 use zksync_config::configs::FriWitnessGeneratorConfig;
 use zksync_dal::ConnectionPool;
-use zksync_object_store::{ClosedFormInputKey, ObjectStore, ObjectStoreFactory};
+use zksync_object_store::{
+    ClosedFormInputKey, EgressMeter, MeteredObjectStore, ObjectStore, ObjectStoreFactory,
+    StoredObject,
+};
 use zksync_queued_job_processor::JobProcessor;
-use zksync_types::proofs::{AggregationRound, LeafAggregationJobMetadata};
+use zksync_types::proofs::{AggregationRound, BaseLayerCircuitId, LeafAggregationJobMetadata};
 use zksync_types::L1BatchNumber;
 
 pub struct LeafAggregationArtifacts {
-    circuit_id: u8,
+    circuit_id: BaseLayerCircuitId,
     block_number: L1BatchNumber,
     aggregations: Vec<(
         u64,
         RecursionQueueSimulator<GoldilocksField>,
         ZkSyncRecursiveLayerCircuit,
     )>,
-    #[allow(dead_code)]
     closed_form_inputs: Vec<ZkSyncBaseLayerClosedFormInput<GoldilocksField>>,
+    base_proofs_count: usize,
+    /// Peak resident memory while the job was processed, if it can be measured on this platform.
+    peak_memory_bytes: Option<u64>,
+    /// Span of the job, carried over from [`LeafAggregationWitnessGeneratorJob`].
+    span: Span,
 }
 
 #[derive(Debug)]
 struct BlobUrls {
     circuit_ids_and_urls: Vec<(u8, String)>,
     aggregations_urls: String,
+    /// Presigned URLs of the prover inputs keyed by their blob URLs,
+    /// if `presigned_url_ttl_in_secs` is set.
+    presigned_circuit_urls: Vec<(String, String)>,
 }
 
+type BaseLayerCircuit = ZkSyncBaseLayerCircuit<
+    GoldilocksField,
+    VmWitnessOracle<GoldilocksField>,
+    ZkSyncDefaultRoundFunction,
+>;
+
 pub struct LeafAggregationWitnessGeneratorJob {
-    circuit_id: u8,
+    id: u32,
+    circuit_id: BaseLayerCircuitId,
     block_number: L1BatchNumber,
     closed_form_inputs: ClosedFormInputWrapper,
     proofs: Vec<ZkSyncBaseLayerProof>,
     base_vk: ZkSyncBaseLayerVerificationKey,
     leaf_params: RecursionLeafParametersWitness<GoldilocksField>,
+    // Circuit the base proofs are verified with, if `verify_proofs_before_aggregation` is set.
+    verification_circuit: Option<BaseLayerCircuit>,
+    /// Span covering the job from its claim until its result is saved; set once the job is claimed.
+    span: Span,
+}
+
+fn job_span(metadata: &LeafAggregationJobMetadata) -> Span {
+    tracing::info_span!(
+        "leaf_aggregation_job",
+        job_id = metadata.id,
+        block_number = metadata.block_number.0,
+        circuit_id = metadata.circuit_id.0,
+        aggregation_round = ?AggregationRound::LeafAggregation,
+    )
+}
+
+/// Expected serialized size of the artifacts produced for a circuit.
+#[derive(Debug, Clone)]
+pub struct ArtifactSizeCheck {
+    expected: RangeInclusive<u64>,
+    fail_on_anomaly: bool,
+}
+
+/// Outcome of validating a single job by [`LeafAggregationWitnessGenerator::validate_queued_jobs()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobValidation {
+    pub job_id: u32,
+    pub block_number: L1BatchNumber,
+    pub circuit_id: BaseLayerCircuitId,
+    /// Error explaining why the job can't be prepared, if it can't.
+    pub result: Result<(), String>,
+}
+
+/// Outcome of [`LeafAggregationWitnessGenerator::validate_queued_jobs()`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationSummary {
+    /// Number of jobs that have all artifacts and proofs in place.
+    pub validated: usize,
+    /// Number of jobs that can't be prepared, e.g. because some of their blobs are missing.
+    pub missing: usize,
+    /// Outcomes of the individual jobs, in the order they are queued.
+    pub jobs: Vec<JobValidation>,
 }
 
 #[derive(Debug)]
 pub struct LeafAggregationWitnessGenerator {
-    #[allow(dead_code)]
     config: FriWitnessGeneratorConfig,
     object_store: Box<dyn ObjectStore>,
     prover_connection_pool: ConnectionPool,
+    resource_usage_writer: Option<ResourceUsageCsvWriter>,
+    metrics_sink: Arc<dyn MetricsSink>,
+    egress_meter: Option<Arc<EgressMeter>>,
+    /// Algorithm of content hashes in the keys of saved aggregations, if they're content-addressed.
+    content_hash_algorithm: Option<ContentHashAlgorithm>,
 }
 
 impl LeafAggregationWitnessGenerator {
@@ -68,17 +148,94 @@ impl LeafAggregationWitnessGenerator {
         config: FriWitnessGeneratorConfig,
         store_factory: &ObjectStoreFactory,
         prover_connection_pool: ConnectionPool,
+        metrics_sink: Arc<dyn MetricsSink>,
     ) -> Self {
+        let resource_usage_writer = config
+            .resource_usage_csv_path
+            .as_ref()
+            .map(|path| ResourceUsageCsvWriter::new(path, config.resource_usage_csv_max_bytes));
+        let mut object_store = store_factory
+            .create_store_for_round(AggregationRound::LeafAggregation)
+            .await;
+        let egress_meter = config
+            .max_egress_bytes_per_hour
+            .map(|max_bytes| Arc::new(EgressMeter::hourly(max_bytes)));
+        if let Some(egress_meter) = &egress_meter {
+            object_store = Box::new(MeteredObjectStore::new(object_store, egress_meter.clone()));
+        }
+        let content_hash_algorithm = config
+            .content_addressed_aggregations
+            .unwrap_or(false)
+            .then(|| store_factory.content_hash_algorithm());
         Self {
             config,
-            object_store: store_factory.create_store().await,
+            object_store,
             prover_connection_pool,
+            resource_usage_writer,
+            metrics_sink,
+            egress_meter,
+            content_hash_algorithm,
+        }
+    }
+
+    /// Prepares every queued job as if it was picked, without picking it or changing anything
+    /// in the database, and reports whether all its artifacts and proofs load and its VKs resolve.
+    /// Used as a pre-flight check before deploys.
+    pub async fn validate_queued_jobs(&self) -> ValidationSummary {
+        let mut prover_connection = self.prover_connection_pool.access_storage().await;
+        let mut transaction = prover_connection.start_read_only_transaction().await;
+        let jobs = transaction
+            .fri_witness_generator_dal()
+            .get_queued_leaf_aggregation_jobs()
+            .await;
+        let verify_proofs = self
+            .config
+            .verify_proofs_before_aggregation
+            .unwrap_or(false);
+
+        let mut summary = ValidationSummary::default();
+        for metadata in jobs {
+            let mut validation = JobValidation {
+                job_id: metadata.id,
+                block_number: metadata.block_number,
+                circuit_id: metadata.circuit_id,
+                result: Ok(()),
+            };
+            let verification_circuit_url = match metadata.prover_job_ids_for_proofs.first() {
+                Some(&prover_job_id) if verify_proofs => {
+                    transaction
+                        .fri_prover_jobs_dal()
+                        .get_circuit_blob_url(prover_job_id)
+                        .await
+                }
+                _ => None,
+            };
+            validation.result = validate_leaf_aggregation_job(prepare_leaf_aggregation_job(
+                metadata,
+                verification_circuit_url,
+                &*self.object_store,
+                &*self.metrics_sink,
+                self.config
+                    .strict_closed_form_input_decoding
+                    .unwrap_or(false),
+                self.config.proof_fetch_concurrency.unwrap_or(1),
+                self.config.strict_proof_job_ids.unwrap_or(false),
+            ))
+            .await;
+            match validation.result {
+                Ok(()) => summary.validated += 1,
+                Err(_) => summary.missing += 1,
+            }
+            summary.jobs.push(validation);
         }
+        summary
     }
 
     fn process_job_sync(
         leaf_job: LeafAggregationWitnessGeneratorJob,
         started_at: Instant,
+        metrics_sink: Arc<dyn MetricsSink>,
+        size_check: Option<ArtifactSizeCheck>,
     ) -> LeafAggregationArtifacts {
         vlog::info!(
             "Starting witness generation of type {:?} for block {} with circuit {}",
@@ -86,7 +243,26 @@ impl LeafAggregationWitnessGenerator {
             leaf_job.block_number.0,
             leaf_job.circuit_id,
         );
-        process_leaf_aggregation_job(started_at, leaf_job)
+        let memory_before = resident_memory_bytes();
+        let mut artifacts =
+            process_leaf_aggregation_job(started_at, leaf_job, &*metrics_sink, size_check.as_ref())
+                .unwrap_or_else(|err| panic!("{}", err));
+        artifacts.peak_memory_bytes = peak_memory_bytes(memory_before);
+        if let Some(peak_memory_bytes) = artifacts.peak_memory_bytes {
+            let labels = [
+                (
+                    "aggregation_round",
+                    format!("{:?}", AggregationRound::LeafAggregation),
+                ),
+                ("circuit_id", artifacts.circuit_id.to_string()),
+            ];
+            metrics_sink.record_histogram(
+                "prover_fri.witness_generation.peak_memory_bytes",
+                peak_memory_bytes as f64,
+                &labels,
+            );
+        }
+        artifacts
     }
 }
 
@@ -99,16 +275,108 @@ impl JobProcessor for LeafAggregationWitnessGenerator {
     const SERVICE_NAME: &'static str = "fri_leaf_aggregation_witness_generator";
 
     async fn get_next_job(&self) -> Option<(Self::JobId, Self::Job)> {
+        if let Some(egress_meter) = &self.egress_meter {
+            if egress_meter.is_exhausted() {
+                vlog::info!(
+                    "Object store egress budget is exhausted, pausing leaf aggregation claims"
+                );
+                return None;
+            }
+        }
+        let worker_settings = self.config.worker_settings();
+        if !worker_settings.picks_jobs() {
+            return None;
+        }
         let mut prover_connection = self.prover_connection_pool.access_storage().await;
+        let circuit_ids = match self.config.queue_depth_shed_threshold {
+            Some(shed_threshold) => {
+                let queue_depth = prover_connection
+                    .fri_witness_generator_dal()
+                    .get_witness_jobs_stats(AggregationRound::LeafAggregation)
+                    .await
+                    .queued;
+                circuit_ids_to_claim(
+                    queue_depth,
+                    shed_threshold,
+                    self.config
+                        .high_priority_circuit_ids
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+            }
+            None => None,
+        };
+        let circuit_ids = restrict_to_allowlist(
+            circuit_ids,
+            worker_settings.circuit_ids_allowlist.as_deref(),
+        );
+        let batch_range = self
+            .config
+            .batch_range()
+            .map(|range| L1BatchNumber(*range.start())..=L1BatchNumber(*range.end()));
         let metadata = prover_connection
             .fri_witness_generator_dal()
-            .get_next_leaf_aggregation_job()
+            .get_next_leaf_aggregation_job(
+                circuit_ids.as_deref(),
+                batch_range,
+                self.config.claim_labels.as_deref(),
+                self.config.retry_backoff_per_job(),
+                self.config.job_ordering.unwrap_or_default(),
+            )
             .await?;
-        vlog::info!("Processing node aggregation job {:?}", metadata.id);
-        Some((
-            metadata.id,
-            prepare_leaf_aggregation_job(metadata, &*self.object_store).await,
-        ))
+        self.metrics_sink.record_histogram(
+            "prover_fri.witness_generation.time_in_queue",
+            time_in_queue(metadata.created_at).as_secs_f64(),
+            &[(
+                "aggregation_round",
+                format!("{:?}", AggregationRound::LeafAggregation),
+            )],
+        );
+        let span = job_span(&metadata);
+        async {
+            vlog::info!("Processing node aggregation job {:?}", metadata.id);
+            let job_id = metadata.id;
+            let started_at = Instant::now();
+            // All proofs of the job are for the same circuit type, so any of their circuits will do.
+            let verify_proofs = self
+                .config
+                .verify_proofs_before_aggregation
+                .unwrap_or(false);
+            let verification_circuit_url = match metadata.prover_job_ids_for_proofs.first() {
+                Some(&prover_job_id) if verify_proofs => {
+                    prover_connection
+                        .fri_prover_jobs_dal()
+                        .get_circuit_blob_url(prover_job_id)
+                        .await
+                }
+                _ => None,
+            };
+            let job = prepare_leaf_aggregation_job(
+                metadata,
+                verification_circuit_url,
+                &*self.object_store,
+                &*self.metrics_sink,
+                self.config
+                    .strict_closed_form_input_decoding
+                    .unwrap_or(false),
+                self.config.proof_fetch_concurrency.unwrap_or(1),
+                self.config.strict_proof_job_ids.unwrap_or(false),
+            )
+            .await;
+            match job {
+                Ok(mut job) => {
+                    job.span = span.clone();
+                    Some((job_id, job))
+                }
+                Err(err) => {
+                    vlog::error!("Failed preparing leaf aggregation job {job_id}: {err}");
+                    self.save_failure(job_id, started_at, err.to_string()).await;
+                    None
+                }
+            }
+        }
+        .instrument(span.clone())
+        .await
     }
 
     async fn save_failure(&self, job_id: u32, _started_at: Instant, error: String) -> () {
@@ -116,7 +384,7 @@ impl JobProcessor for LeafAggregationWitnessGenerator {
             .access_storage()
             .await
             .fri_witness_generator_dal()
-            .mark_leaf_aggregation_job_failed(&error, job_id)
+            .mark_leaf_aggregation_job_failed(&error, job_id, self.config.max_attempts)
             .await;
     }
 
@@ -126,7 +394,18 @@ impl JobProcessor for LeafAggregationWitnessGenerator {
         job: LeafAggregationWitnessGeneratorJob,
         started_at: Instant,
     ) -> tokio::task::JoinHandle<LeafAggregationArtifacts> {
-        tokio::task::spawn_blocking(move || Self::process_job_sync(job, started_at))
+        let metrics_sink = self.metrics_sink.clone();
+        let size_check = self
+            .config
+            .artifact_size_range(job.circuit_id.0)
+            .map(|expected| ArtifactSizeCheck {
+                expected,
+                fail_on_anomaly: self.config.fail_on_artifact_size_anomaly.unwrap_or(false),
+            });
+        let span = job.span.clone();
+        tokio::task::spawn_blocking(move || {
+            span.in_scope(|| Self::process_job_sync(job, started_at, metrics_sink, size_check))
+        })
     }
 
     async fn save_result(
@@ -135,38 +414,205 @@ impl JobProcessor for LeafAggregationWitnessGenerator {
         started_at: Instant,
         artifacts: LeafAggregationArtifacts,
     ) {
-        let block_number = artifacts.block_number;
-        let circuit_id = artifacts.circuit_id;
-        let blob_urls = save_artifacts(artifacts, &*self.object_store).await;
-        update_database(
-            &self.prover_connection_pool,
-            started_at,
-            block_number,
-            job_id,
-            blob_urls,
-            circuit_id,
-        )
-        .await;
+        let span = artifacts.span.clone();
+        async {
+            let block_number = artifacts.block_number;
+            let circuit_id = artifacts.circuit_id;
+            let aggregations_count = artifacts.aggregations.len();
+            let resource_usage = self
+                .resource_usage_writer
+                .as_ref()
+                .map(|_| resource_usage(&artifacts, started_at));
+            let blob_urls = save_artifacts(
+                job_id,
+                artifacts,
+                &*self.object_store,
+                &*self.metrics_sink,
+                self.config.artifact_save_concurrency.unwrap_or(1),
+                self.config.presigned_url_ttl(),
+                self.content_hash_algorithm,
+            )
+            .await;
+            if self.config.verify_artifacts_before_commit.unwrap_or(false) {
+                let verification = verify_saved_artifacts(
+                    job_id,
+                    &blob_urls,
+                    aggregations_count,
+                    &*self.object_store,
+                )
+                .await;
+                if let Err(err) = verification {
+                    vlog::error!(
+                        "Failed verifying artifacts of leaf aggregation job {job_id}: {err}"
+                    );
+                    self.save_failure(job_id, started_at, err.to_string()).await;
+                    return;
+                }
+            }
+            update_database(
+                &self.prover_connection_pool,
+                started_at,
+                block_number,
+                job_id,
+                blob_urls,
+                circuit_id,
+            )
+            .await;
+            if let (Some(writer), Some(resource_usage)) =
+                (&self.resource_usage_writer, resource_usage)
+            {
+                if let Err(err) = writer.append(&resource_usage) {
+                    vlog::warn!("Failed to export resource usage of job {job_id}: {err}");
+                }
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    fn overlap_claim_with_save(&self) -> bool {
+        self.config.overlap_claim_with_save.unwrap_or(false)
+    }
+
+    fn prefetch_next_job(&self) -> bool {
+        self.config.prefetch_next_job.unwrap_or(false)
+    }
+
+    fn job_timeout(&self) -> Option<Duration> {
+        self.config.job_timeout()
+    }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        Some(self.config.witness_generation_timeout())
+    }
+}
+
+fn resource_usage(artifacts: &LeafAggregationArtifacts, started_at: Instant) -> JobResourceUsage {
+    JobResourceUsage {
+        circuit_id: artifacts.circuit_id.0,
+        input_count: artifacts.closed_form_inputs.len(),
+        base_proofs_count: artifacts.base_proofs_count,
+        duration: started_at.elapsed(),
+        peak_memory_bytes: artifacts.peak_memory_bytes,
+        artifact_bytes: bincode::serialized_size(&artifacts.aggregations)
+            .expect("Failed computing serialized size of aggregations"),
+    }
+}
+
+/// Returns the circuit ids a worker may pick jobs for (`None` meaning all circuits).
+/// Once the queue is deeper than `shed_threshold`, low-priority circuits are shed until it drains.
+/// Without high-priority circuits, nothing is shed; otherwise, no worker would claim any jobs
+/// and the queue would never drain below the threshold.
+fn circuit_ids_to_claim(
+    queue_depth: usize,
+    shed_threshold: usize,
+    high_priority_circuit_ids: &[u8],
+) -> Option<&[u8]> {
+    let shed = queue_depth > shed_threshold && !high_priority_circuit_ids.is_empty();
+    shed.then_some(high_priority_circuit_ids)
+}
+
+/// Narrows down the circuits to claim to the circuit allowlist of the worker, if any.
+fn restrict_to_allowlist(circuit_ids: Option<&[u8]>, allowlist: Option<&[u8]>) -> Option<Vec<u8>> {
+    match (circuit_ids, allowlist) {
+        (None, None) => None,
+        (Some(circuit_ids), None) | (None, Some(circuit_ids)) => Some(circuit_ids.to_vec()),
+        (Some(circuit_ids), Some(allowlist)) => Some(
+            circuit_ids
+                .iter()
+                .copied()
+                .filter(|circuit_id| allowlist.contains(circuit_id))
+                .collect(),
+        ),
+    }
+}
+
+/// Awaits the job preparation and discards the prepared job. VKs are loaded during preparation
+/// and panic if they can't be resolved, so panics are reported as validation errors as well.
+async fn validate_leaf_aggregation_job<F>(prepare_job: F) -> Result<(), String>
+where
+    F: Future<Output = Result<LeafAggregationWitnessGeneratorJob, WitnessGeneratorError>>,
+{
+    match AssertUnwindSafe(prepare_job).catch_unwind().await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            Err(format!("panicked while preparing the job: {message}"))
+        }
     }
 }
 
+#[tracing::instrument(
+    skip_all,
+    fields(
+        job_id = metadata.id,
+        block_number = metadata.block_number.0,
+        circuit_id = metadata.circuit_id.0,
+        aggregation_round = ?AggregationRound::LeafAggregation,
+    )
+)]
 async fn prepare_leaf_aggregation_job(
     metadata: LeafAggregationJobMetadata,
+    verification_circuit_url: Option<String>,
     object_store: &dyn ObjectStore,
-) -> LeafAggregationWitnessGeneratorJob {
+    metrics_sink: &dyn MetricsSink,
+    strict_decoding: bool,
+    proof_fetch_concurrency: usize,
+    strict_proof_job_ids: bool,
+) -> Result<LeafAggregationWitnessGeneratorJob, WitnessGeneratorError> {
+    // A malformed queue entry may have no proofs; there is nothing to aggregate then.
+    if metadata.prover_job_ids_for_proofs.is_empty() {
+        return Err(WitnessGeneratorError::NoProofs {
+            job_id: metadata.id,
+        });
+    }
     let started_at = Instant::now();
-    let closed_form_input = get_artifacts(&metadata, object_store).await;
-    let proofs = load_proofs_for_job_ids(&metadata.prover_job_ids_for_proofs, object_store).await;
-    metrics::histogram!(
+    let closed_form_input = get_artifacts(&metadata, object_store, strict_decoding).await?;
+    let proofs = load_proofs_for_job_ids(
+        &metadata.prover_job_ids_for_proofs,
+        object_store,
+        AggregationRound::LeafAggregation,
+        proof_fetch_concurrency,
+        strict_proof_job_ids,
+    )
+    .await
+    .map_err(|err| match err {
+        LoadProofsError::Fetch { job_id, err } => {
+            WitnessGeneratorError::artifact_fetch(FriProofWrapper::encode_key(job_id), err)
+        }
+        LoadProofsError::DuplicateProofJobId { id } => {
+            WitnessGeneratorError::DuplicateProofJobId { id }
+        }
+    })?;
+    let verification_circuit = match verification_circuit_url {
+        Some(url) => match object_store.get_by_encoded_key(&url).await {
+            Ok(CircuitWrapper::Base(circuit)) => Some(circuit),
+            Ok(CircuitWrapper::Recursive(_)) => {
+                panic!(
+                    "Expected a base layer circuit at {url} for leaf agg {}",
+                    metadata.id
+                )
+            }
+            Err(err) => return Err(WitnessGeneratorError::artifact_fetch(url, err)),
+        },
+        None => None,
+    };
+    report_leaf_aggregation_time(
+        metrics_sink,
         "prover_fri.witness_generation.blob_fetch_time",
+        metadata.circuit_id,
         started_at.elapsed(),
-        "aggregation_round" => format!("{:?}", AggregationRound::LeafAggregation),
     );
     let started_at = Instant::now();
-    let base_vk = get_base_layer_vk_for_circuit_type(metadata.circuit_id);
-    // this is a temp solution to unblock shadow proving.
-    // we should have a method that converts basic circuit id to leaf circuit id as they are different.
-    let leaf_vk = get_recursive_layer_vk_for_circuit_type(metadata.circuit_id + 2);
+    let base_vk = get_base_layer_vk_for_circuit_type(metadata.circuit_id.0);
+    let leaf_vk = get_recursive_layer_vk_for_circuit_type(
+        get_recursive_layer_circuit_id_for_base_layer(metadata.circuit_id).0,
+    );
     let base_proofs = proofs
         .into_iter()
         .map(|wrapper| match wrapper {
@@ -176,39 +622,76 @@ async fn prepare_leaf_aggregation_job(
             }
         })
         .collect::<Vec<_>>();
-    let leaf_params = compute_leaf_params(metadata.circuit_id, base_vk.clone(), leaf_vk);
-    metrics::histogram!(
+    let leaf_params = compute_leaf_params(metadata.circuit_id.0, base_vk.clone(), leaf_vk);
+    report_leaf_aggregation_time(
+        metrics_sink,
         "prover_fri.witness_generation.prepare_job_time",
+        metadata.circuit_id,
         started_at.elapsed(),
-        "aggregation_round" => format!("{:?}", AggregationRound::LeafAggregation),
     );
-    LeafAggregationWitnessGeneratorJob {
+    Ok(LeafAggregationWitnessGeneratorJob {
+        id: metadata.id,
         circuit_id: metadata.circuit_id,
         block_number: metadata.block_number,
         closed_form_inputs: closed_form_input,
         proofs: base_proofs,
         base_vk,
         leaf_params,
-    }
+        verification_circuit,
+        span: Span::none(),
+    })
 }
 
+#[tracing::instrument(
+    skip_all,
+    fields(
+        job_id = job.id,
+        block_number = job.block_number.0,
+        circuit_id = job.circuit_id.0,
+        aggregation_round = ?AggregationRound::LeafAggregation,
+    )
+)]
 pub fn process_leaf_aggregation_job(
     started_at: Instant,
     job: LeafAggregationWitnessGeneratorJob,
-) -> LeafAggregationArtifacts {
+    metrics_sink: &dyn MetricsSink,
+    size_check: Option<&ArtifactSizeCheck>,
+) -> Result<LeafAggregationArtifacts, WitnessGeneratorError> {
     let circuit_id = job.circuit_id;
+    let base_proofs_count = job.proofs.len();
+    // Proofs are missing if the dependencies of the job weren't tracked correctly.
+    check_proofs_present(base_proofs_count, job.closed_form_inputs.0.len())?;
+    if let Some(circuit) = &job.verification_circuit {
+        verify_base_proofs(job.id, circuit, &job.proofs, &job.base_vk)?;
+    }
     let subsets = (
-        circuit_id as u64,
+        circuit_id.0 as u64,
         job.closed_form_inputs.1,
         job.closed_form_inputs.0,
     );
-    let leaf_params = (circuit_id, job.leaf_params);
+    let leaf_params = (circuit_id.0, job.leaf_params);
     let (aggregations, closed_form_inputs) =
         create_leaf_witnesses(subsets, job.proofs, job.base_vk, leaf_params);
-    metrics::histogram!(
+    validate_recursion_queues(aggregations.iter().map(|(_, queue, _)| queue))?;
+    // Every base proof must end up in exactly one recursion queue; otherwise proofs were dropped silently.
+    verify_aggregation_count(
+        base_proofs_count,
+        aggregations
+            .iter()
+            .map(|(_, queue, _)| queue.num_items as usize),
+    )?;
+    // The number of aggregations is the fan-out of the job into the node aggregation round.
+    report_aggregations_produced(metrics_sink, circuit_id, aggregations.len());
+    if let Some(size_check) = size_check {
+        let size = bincode::serialized_size(&aggregations)
+            .expect("Failed computing serialized size of aggregations");
+        check_artifact_size(circuit_id, size, size_check, metrics_sink)?;
+    }
+    report_leaf_aggregation_time(
+        metrics_sink,
         "prover_fri.witness_generation.witness_generation_time",
+        circuit_id,
         started_at.elapsed(),
-        "aggregation_round" => format!("{:?}", AggregationRound::LeafAggregation),
     );
     vlog::info!(
         "Leaf witness generation for block {} with circuit id {}: is complete in {:?}.",
@@ -217,25 +700,203 @@ pub fn process_leaf_aggregation_job(
         started_at.elapsed(),
     );
 
-    LeafAggregationArtifacts {
+    Ok(LeafAggregationArtifacts {
         circuit_id,
         block_number: job.block_number,
         aggregations,
         closed_form_inputs,
+        base_proofs_count,
+        peak_memory_bytes: None,
+        span: job.span,
+    })
+}
+
+/// Verifies the base proofs before aggregating them, so that a corrupted or mismatched proof
+/// fails the job with a clear error instead of deep inside the witness generation.
+/// `circuit` is only used to configure the verifier for the circuit type of the proofs.
+fn verify_base_proofs(
+    job_id: u32,
+    circuit: &BaseLayerCircuit,
+    proofs: &[ZkSyncBaseLayerProof],
+    vk: &ZkSyncBaseLayerVerificationKey,
+) -> Result<(), WitnessGeneratorError> {
+    for (index, proof) in proofs.iter().enumerate() {
+        if !verify_base_layer_proof::<NoPow>(circuit, proof, vk) {
+            return Err(WitnessGeneratorError::ProofVerificationFailed { job_id, index });
+        }
     }
+    Ok(())
 }
 
+fn report_leaf_aggregation_time(
+    metrics_sink: &dyn MetricsSink,
+    name: &'static str,
+    circuit_id: BaseLayerCircuitId,
+    elapsed: Duration,
+) {
+    let labels = [
+        (
+            "aggregation_round",
+            format!("{:?}", AggregationRound::LeafAggregation),
+        ),
+        ("circuit_id", circuit_id.to_string()),
+    ];
+    metrics_sink.record_histogram(name, elapsed.as_secs_f64(), &labels);
+}
+
+fn report_aggregations_produced(
+    metrics_sink: &dyn MetricsSink,
+    circuit_id: BaseLayerCircuitId,
+    count: usize,
+) {
+    metrics_sink.record_histogram(
+        "prover_fri.witness_generation.aggregations_produced",
+        count as f64,
+        &[("circuit_id", circuit_id.to_string())],
+    );
+}
+
+fn check_artifact_size(
+    circuit_id: BaseLayerCircuitId,
+    size: u64,
+    size_check: &ArtifactSizeCheck,
+    metrics_sink: &dyn MetricsSink,
+) -> Result<(), WitnessGeneratorError> {
+    if size_check.expected.contains(&size) {
+        return Ok(());
+    }
+    metrics_sink.increment_counter(
+        "prover_fri.artifact_size_anomaly",
+        1,
+        &[("circuit_id", circuit_id.to_string())],
+    );
+    let err = WitnessGeneratorError::ArtifactSizeAnomaly {
+        circuit_id,
+        size,
+        expected: size_check.expected.clone(),
+    };
+    if size_check.fail_on_anomaly {
+        return Err(err);
+    }
+    vlog::warn!("{}", err);
+    Ok(())
+}
+
+fn check_proofs_present(
+    proofs_count: usize,
+    closed_form_inputs_count: usize,
+) -> Result<(), WitnessGeneratorError> {
+    if proofs_count == 0 && closed_form_inputs_count > 0 {
+        return Err(WitnessGeneratorError::NoProofsForNonEmptyJob {
+            closed_form_inputs_count,
+        });
+    }
+    Ok(())
+}
+
+fn verify_aggregation_count(
+    expected: usize,
+    aggregated_counts: impl IntoIterator<Item = usize>,
+) -> Result<(), WitnessGeneratorError> {
+    let got = aggregated_counts.into_iter().sum();
+    if got != expected {
+        return Err(WitnessGeneratorError::AggregationCountMismatch { expected, got });
+    }
+    Ok(())
+}
+
+/// Checks that the recursion queues are consistent before they're saved for the node aggregation round,
+/// which would otherwise be the first to fail on them.
+pub(crate) fn validate_recursion_queues<'a>(
+    queues: impl IntoIterator<Item = &'a RecursionQueueSimulator<GoldilocksField>>,
+) -> Result<(), WitnessGeneratorError> {
+    for (index, queue) in queues.into_iter().enumerate() {
+        if queue.num_items as usize != queue.witness.len() {
+            return Err(WitnessGeneratorError::InvalidRecursionQueue {
+                index,
+                num_items: queue.num_items,
+                witness_len: queue.witness.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Reads back the artifacts saved for a job and checks that they decode to `aggregations_count`
+/// consistent aggregations and recursive circuits, so that the job is only committed once
+/// its output is known to be usable by the node aggregation round.
+async fn verify_saved_artifacts(
+    job_id: u32,
+    blob_urls: &BlobUrls,
+    aggregations_count: usize,
+    object_store: &dyn ObjectStore,
+) -> Result<(), WitnessGeneratorError> {
+    let failure = |key: &str, reason: String| WitnessGeneratorError::ArtifactVerificationFailed {
+        job_id,
+        key: key.to_owned(),
+        reason,
+    };
+
+    let key = &blob_urls.aggregations_urls;
+    let bytes = object_store
+        .get_raw(AggregationWrapper::BUCKET, key)
+        .await
+        .map_err(|err| failure(key, err.to_string()))?;
+    let aggregations =
+        AggregationWrapper::deserialize(bytes).map_err(|err| failure(key, err.to_string()))?;
+    if aggregations.0.len() != aggregations_count {
+        let reason = format!(
+            "expected {aggregations_count} aggregations, got {}",
+            aggregations.0.len()
+        );
+        return Err(failure(key, reason));
+    }
+    validate_recursion_queues(aggregations.0.iter().map(|(_, queue, _)| queue))
+        .map_err(|err| failure(key, err.to_string()))?;
+
+    if blob_urls.circuit_ids_and_urls.len() != aggregations_count {
+        let reason = format!(
+            "expected {aggregations_count} recursive circuits, got {}",
+            blob_urls.circuit_ids_and_urls.len()
+        );
+        return Err(failure(key, reason));
+    }
+    for (_, key) in &blob_urls.circuit_ids_and_urls {
+        let bytes = object_store
+            .get_raw(CircuitWrapper::BUCKET, key)
+            .await
+            .map_err(|err| failure(key, err.to_string()))?;
+        match CircuitWrapper::deserialize(bytes).map_err(|err| failure(key, err.to_string()))? {
+            CircuitWrapper::Recursive(_) => {}
+            CircuitWrapper::Base(_) => {
+                return Err(failure(key, "expected a recursive circuit".to_owned()))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        job_id = job_id,
+        block_number = block_number.0,
+        circuit_id = circuit_id.0,
+        aggregation_round = ?AggregationRound::LeafAggregation,
+    )
+)]
 async fn update_database(
     prover_connection_pool: &ConnectionPool,
     started_at: Instant,
     block_number: L1BatchNumber,
     job_id: u32,
     blob_urls: BlobUrls,
-    circuit_id: u8,
+    circuit_id: BaseLayerCircuitId,
 ) {
     let mut prover_connection = prover_connection_pool.access_storage().await;
     let mut transaction = prover_connection.start_transaction().await;
     let number_of_dependent_jobs = blob_urls.circuit_ids_and_urls.len();
+    let node_circuit_id = get_recursive_layer_circuit_id_for_base_layer(circuit_id);
     transaction
         .fri_prover_jobs_dal()
         .insert_prover_jobs(
@@ -245,16 +906,30 @@ async fn update_database(
             0,
         )
         .await;
+    if !blob_urls.presigned_circuit_urls.is_empty() {
+        transaction
+            .fri_prover_jobs_dal()
+            .set_circuit_presigned_urls(
+                block_number,
+                AggregationRound::LeafAggregation,
+                blob_urls.presigned_circuit_urls,
+            )
+            .await;
+    }
     transaction
         .fri_witness_generator_dal()
         .update_node_aggregation_jobs_url(
             block_number,
-            get_recursive_layer_circuit_id_for_base_layer(circuit_id),
+            node_circuit_id.0,
             number_of_dependent_jobs,
             0,
             blob_urls.aggregations_urls,
         )
         .await;
+    transaction
+        .fri_witness_generator_dal()
+        .insert_node_aggregation_job_dependencies(block_number, node_circuit_id.0, 0)
+        .await;
     transaction
         .fri_witness_generator_dal()
         .mark_leaf_aggregation_as_successful(job_id, started_at.elapsed())
@@ -266,46 +941,508 @@ async fn update_database(
 async fn get_artifacts(
     metadata: &LeafAggregationJobMetadata,
     object_store: &dyn ObjectStore,
-) -> ClosedFormInputWrapper {
+    strict_decoding: bool,
+) -> Result<ClosedFormInputWrapper, WitnessGeneratorError> {
     let key = ClosedFormInputKey {
         block_number: metadata.block_number,
-        circuit_id: metadata.circuit_id,
+        circuit_id: metadata.circuit_id.0,
     };
-    object_store
-        .get(key)
+    let encoded_key = ClosedFormInputWrapper::encode_key(key);
+    let bytes = object_store
+        .get_raw(ClosedFormInputWrapper::BUCKET, &encoded_key)
         .await
-        .unwrap_or_else(|_| panic!("leaf aggregation job artifacts missing: {:?}", key))
+        .map_err(|err| WitnessGeneratorError::artifact_fetch(encoded_key, err))?;
+    let closed_form_input = ClosedFormInputWrapper::deserialize_with_mode(&bytes, strict_decoding)
+        .unwrap_or_else(|err| {
+            panic!("failed deserializing leaf aggregation job artifacts {key:?}: {err}")
+        });
+    Ok(closed_form_input)
 }
 
+#[tracing::instrument(
+    skip_all,
+    fields(
+        job_id = job_id,
+        block_number = artifacts.block_number.0,
+        circuit_id = artifacts.circuit_id.0,
+        aggregation_round = ?AggregationRound::LeafAggregation,
+    )
+)]
 async fn save_artifacts(
+    job_id: u32,
     artifacts: LeafAggregationArtifacts,
     object_store: &dyn ObjectStore,
+    metrics_sink: &dyn MetricsSink,
+    concurrency: usize,
+    presigned_url_ttl: Option<Duration>,
+    content_hash_algorithm: Option<ContentHashAlgorithm>,
 ) -> BlobUrls {
     let started_at = Instant::now();
-    let aggregations_urls = save_node_aggregations_artifacts(
-        artifacts.block_number,
-        get_recursive_layer_circuit_id_for_base_layer(artifacts.circuit_id),
-        0,
-        artifacts.aggregations.clone(),
-        object_store,
-    )
-    .await;
-    let circuit_ids_and_urls = save_recursive_layer_prover_input_artifacts(
-        artifacts.block_number,
-        artifacts.aggregations,
-        AggregationRound::LeafAggregation,
-        0,
-        object_store,
-        None,
-    )
-    .await;
-    metrics::histogram!(
+    let circuit_id = artifacts.circuit_id;
+    // Both saves are dominated by the object store latency, so they're run concurrently.
+    let (aggregations_urls, circuit_ids_and_urls) = futures::join!(
+        save_node_aggregations_artifacts(
+            artifacts.block_number,
+            get_recursive_layer_circuit_id_for_base_layer(circuit_id).0,
+            0,
+            artifacts.aggregations.clone(),
+            AggregationRound::LeafAggregation,
+            object_store,
+            content_hash_algorithm,
+        ),
+        save_recursive_layer_prover_input_artifacts(
+            artifacts.block_number,
+            artifacts.aggregations,
+            AggregationRound::LeafAggregation,
+            0,
+            object_store,
+            None,
+            concurrency,
+        )
+    );
+    report_leaf_aggregation_time(
+        metrics_sink,
         "prover_fri.witness_generation.blob_save_time",
+        circuit_id,
         started_at.elapsed(),
-        "aggregation_round" => format!("{:?}", AggregationRound::LeafAggregation),
     );
+    let presigned_circuit_urls = match presigned_url_ttl {
+        Some(ttl) => presign_circuit_urls(&circuit_ids_and_urls, object_store, ttl).await,
+        None => vec![],
+    };
     BlobUrls {
         circuit_ids_and_urls,
         aggregations_urls,
+        presigned_circuit_urls,
+    }
+}
+
+/// Presigns URLs of the saved prover inputs, so that provers can fetch them without proxying.
+/// The URLs are persisted on the prover jobs (see `FriProverDal::get_circuit_presigned_url()`).
+/// Presigning is best-effort: no URLs are returned if the object store fails to presign any of them.
+async fn presign_circuit_urls(
+    circuit_ids_and_urls: &[(u8, String)],
+    object_store: &dyn ObjectStore,
+    ttl: Duration,
+) -> Vec<(String, String)> {
+    let mut presigned_urls = Vec::with_capacity(circuit_ids_and_urls.len());
+    for (_, blob_url) in circuit_ids_and_urls {
+        match object_store
+            .get_presigned_url(CircuitWrapper::BUCKET, blob_url, ttl)
+            .await
+        {
+            Ok(url) => presigned_urls.push((blob_url.clone(), url)),
+            Err(err) => {
+                vlog::warn!("Failed presigning URL of prover input {blob_url}: {err}");
+                return vec![];
+            }
+        }
+    }
+    presigned_urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics_sink::RecordingSink;
+    use zksync_config::configs::fri_witness_generator::WorkerRole;
+    use zksync_object_store::{AggregationsKey, ObjectStoreError};
+
+    #[test]
+    fn leaf_aggregation_time_is_routed_to_sink() {
+        let sink = RecordingSink::default();
+        report_leaf_aggregation_time(
+            &sink,
+            "prover_fri.witness_generation.blob_save_time",
+            BaseLayerCircuitId(3),
+            Duration::from_millis(1500),
+        );
+        let metrics = sink.metrics.into_inner().unwrap();
+        assert_eq!(
+            metrics,
+            [(
+                "prover_fri.witness_generation.blob_save_time",
+                1.5,
+                vec![
+                    ("aggregation_round", "LeafAggregation".to_owned()),
+                    ("circuit_id", "3".to_owned())
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn produced_aggregations_are_reported() {
+        let sink = RecordingSink::default();
+        report_aggregations_produced(&sink, BaseLayerCircuitId(3), 4);
+        let metrics = sink.metrics.into_inner().unwrap();
+        assert_eq!(
+            metrics,
+            [(
+                "prover_fri.witness_generation.aggregations_produced",
+                4.0,
+                vec![("circuit_id", "3".to_owned())]
+            )]
+        );
+    }
+
+    #[test]
+    fn artifact_size_out_of_range_is_reported() {
+        let sink = RecordingSink::default();
+        let size_check = ArtifactSizeCheck {
+            expected: 1_000..=2_000,
+            fail_on_anomaly: false,
+        };
+        let circuit_id = BaseLayerCircuitId(3);
+        check_artifact_size(circuit_id, 1_500, &size_check, &sink).unwrap();
+        assert!(sink.metrics.lock().unwrap().is_empty());
+
+        check_artifact_size(circuit_id, 50, &size_check, &sink).unwrap();
+        let failing_check = ArtifactSizeCheck {
+            fail_on_anomaly: true,
+            ..size_check
+        };
+        let err = check_artifact_size(circuit_id, 50_000, &failing_check, &sink).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::ArtifactSizeAnomaly {
+                circuit_id: BaseLayerCircuitId(3),
+                size: 50_000,
+                ..
+            }
+        ));
+
+        let metrics = sink.metrics.into_inner().unwrap();
+        let anomaly = (
+            "prover_fri.artifact_size_anomaly",
+            1.0,
+            vec![("circuit_id", "3".to_owned())],
+        );
+        assert_eq!(metrics, [anomaly.clone(), anomaly]);
+    }
+
+    #[test]
+    fn aggregation_count_matches_proofs() {
+        assert!(verify_aggregation_count(5, vec![2, 2, 1]).is_ok());
+    }
+
+    #[test]
+    fn aggregation_count_mismatch_is_detected() {
+        // Simulates `create_leaf_witnesses` dropping one of the five input proofs.
+        let err = verify_aggregation_count(5, vec![2, 2]).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::AggregationCountMismatch {
+                expected: 5,
+                got: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn empty_proofs_for_non_empty_inputs_are_rejected() {
+        assert!(check_proofs_present(0, 0).is_ok());
+        assert!(check_proofs_present(2, 2).is_ok());
+        let err = check_proofs_present(0, 2).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::NoProofsForNonEmptyJob {
+                closed_form_inputs_count: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn inconsistent_recursion_queue_is_rejected() {
+        let valid_queue = RecursionQueueSimulator::empty();
+        let mut invalid_queue = RecursionQueueSimulator::empty();
+        invalid_queue.num_items = 2;
+        assert!(validate_recursion_queues([&valid_queue]).is_ok());
+
+        let err = validate_recursion_queues([&valid_queue, &invalid_queue]).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::InvalidRecursionQueue {
+                index: 1,
+                num_items: 2,
+                witness_len: 0
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_artifacts_are_reported_as_errors() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let metadata = LeafAggregationJobMetadata {
+            id: 1,
+            block_number: L1BatchNumber(1),
+            circuit_id: BaseLayerCircuitId(3),
+            prover_job_ids_for_proofs: vec![],
+            attempts: 1,
+            created_at: chrono::Utc::now(),
+        };
+        let err = get_artifacts(&metadata, &*object_store, false)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WitnessGeneratorError::ArtifactMissing { .. }));
+        assert_eq!(
+            err.to_string(),
+            "leaf aggregation job artifacts missing: closed_form_inputs_1_3.bin"
+        );
+
+        let err = load_proofs_for_job_ids(
+            &[5],
+            &*object_store,
+            AggregationRound::LeafAggregation,
+            1,
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, LoadProofsError::Fetch { job_id: 5, .. }));
+    }
+
+    #[test]
+    fn only_missing_blobs_are_reported_as_missing_artifacts() {
+        let err = WitnessGeneratorError::artifact_fetch(
+            "proof_5.bin".to_owned(),
+            ObjectStoreError::KeyNotFound("not found".into()),
+        );
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::ArtifactMissing { ref key } if key == "proof_5.bin"
+        ));
+
+        let err = WitnessGeneratorError::artifact_fetch(
+            "proof_5.bin".to_owned(),
+            ObjectStoreError::Other("permission denied".into()),
+        );
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::ArtifactFetchFailed {
+                ref key,
+                err: ObjectStoreError::Other(_),
+            } if key == "proof_5.bin"
+        ));
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.to_string().contains("permission denied"), "{source}");
+    }
+
+    #[tokio::test]
+    async fn job_without_proofs_is_rejected() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let metadata = LeafAggregationJobMetadata {
+            id: 1,
+            block_number: L1BatchNumber(1),
+            circuit_id: BaseLayerCircuitId(3),
+            prover_job_ids_for_proofs: vec![],
+            attempts: 1,
+            created_at: chrono::Utc::now(),
+        };
+        let result = prepare_leaf_aggregation_job(
+            metadata,
+            None,
+            &*object_store,
+            &RecordingSink::default(),
+            false,
+            1,
+            false,
+        )
+        .await;
+        let err = result.err().unwrap();
+        assert!(matches!(err, WitnessGeneratorError::NoProofs { job_id: 1 }));
+        assert_eq!(err.to_string(), "leaf aggregation job 1 has no proofs");
+    }
+
+    #[tokio::test]
+    async fn validation_reports_missing_artifacts_and_panics() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let metadata = LeafAggregationJobMetadata {
+            id: 1,
+            block_number: L1BatchNumber(1),
+            circuit_id: BaseLayerCircuitId(3),
+            prover_job_ids_for_proofs: vec![5],
+            attempts: 0,
+            created_at: chrono::Utc::now(),
+        };
+        let err = validate_leaf_aggregation_job(prepare_leaf_aggregation_job(
+            metadata,
+            None,
+            &*object_store,
+            &RecordingSink::default(),
+            false,
+            1,
+            false,
+        ))
+        .await
+        .unwrap_err();
+        assert!(
+            err.starts_with("leaf aggregation job artifacts missing"),
+            "{err}"
+        );
+
+        async fn prepare_job_without_vk(
+        ) -> Result<LeafAggregationWitnessGeneratorJob, WitnessGeneratorError> {
+            panic!("VK for circuit 3 is missing")
+        }
+        let err = validate_leaf_aggregation_job(prepare_job_without_vk())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "panicked while preparing the job: VK for circuit 3 is missing"
+        );
+    }
+
+    #[tokio::test]
+    async fn presigned_urls_are_skipped_if_unsupported() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let circuit_ids_and_urls = [(3, "1_0_3_LeafAggregation_0.bin".to_owned())];
+        let presigned_urls = presign_circuit_urls(
+            &circuit_ids_and_urls,
+            &*object_store,
+            Duration::from_secs(60),
+        )
+        .await;
+        assert!(presigned_urls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn artifacts_are_saved_with_blob_save_time() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let sink = RecordingSink::default();
+        let artifacts = LeafAggregationArtifacts {
+            circuit_id: BaseLayerCircuitId(1),
+            block_number: L1BatchNumber(1),
+            aggregations: vec![],
+            closed_form_inputs: vec![],
+            base_proofs_count: 0,
+            peak_memory_bytes: None,
+            span: Span::none(),
+        };
+        let blob_urls = save_artifacts(
+            1,
+            artifacts,
+            &*object_store,
+            &sink,
+            2,
+            None,
+            Some(ContentHashAlgorithm::Sha256),
+        )
+        .await;
+
+        assert!(blob_urls.circuit_ids_and_urls.is_empty());
+        let key = AggregationsKey {
+            block_number: L1BatchNumber(1),
+            circuit_id: get_recursive_layer_circuit_id_for_base_layer(BaseLayerCircuitId(1)).0,
+            depth: 0,
+        };
+        let legacy_key = AggregationWrapper::encode_key(key);
+        assert_ne!(blob_urls.aggregations_urls, legacy_key);
+        assert!(blob_urls
+            .aggregations_urls
+            .starts_with(legacy_key.trim_end_matches(".bin")));
+        let saved: AggregationWrapper = object_store
+            .get_verified_by_content_key(&blob_urls.aggregations_urls)
+            .await
+            .unwrap();
+        assert!(saved.0.is_empty());
+        let metrics = sink.metrics.into_inner().unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].0, "prover_fri.witness_generation.blob_save_time");
+    }
+
+    #[tokio::test]
+    async fn saved_artifacts_are_verified() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let sink = RecordingSink::default();
+        let artifacts = LeafAggregationArtifacts {
+            circuit_id: BaseLayerCircuitId(1),
+            block_number: L1BatchNumber(1),
+            aggregations: vec![],
+            closed_form_inputs: vec![],
+            base_proofs_count: 0,
+            peak_memory_bytes: None,
+            span: Span::none(),
+        };
+        let blob_urls = save_artifacts(1, artifacts, &*object_store, &sink, 1, None, None).await;
+        verify_saved_artifacts(1, &blob_urls, 0, &*object_store)
+            .await
+            .unwrap();
+
+        // A job whose output doesn't read back must be failed rather than committed.
+        let err = verify_saved_artifacts(1, &blob_urls, 2, &*object_store)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessGeneratorError::ArtifactVerificationFailed { job_id: 1, .. }
+        ));
+        object_store
+            .put_raw(
+                AggregationWrapper::BUCKET,
+                &blob_urls.aggregations_urls,
+                vec![0xff; 3],
+            )
+            .await
+            .unwrap();
+        let err = verify_saved_artifacts(1, &blob_urls, 0, &*object_store)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with(&format!(
+            "saved artifact {} of job 1",
+            blob_urls.aggregations_urls
+        )));
+    }
+
+    #[test]
+    fn low_priority_circuits_are_shed_above_threshold() {
+        let high_priority_circuit_ids = [1, 3];
+        assert_eq!(
+            circuit_ids_to_claim(10, 10, &high_priority_circuit_ids),
+            None
+        );
+        assert_eq!(
+            circuit_ids_to_claim(11, 10, &high_priority_circuit_ids),
+            Some(&high_priority_circuit_ids[..])
+        );
+    }
+
+    #[test]
+    fn resource_usage_includes_peak_memory() {
+        let artifacts = LeafAggregationArtifacts {
+            circuit_id: BaseLayerCircuitId(3),
+            block_number: L1BatchNumber(1),
+            aggregations: vec![],
+            closed_form_inputs: vec![],
+            base_proofs_count: 2,
+            peak_memory_bytes: Some(4096),
+            span: Span::none(),
+        };
+        let usage = resource_usage(&artifacts, Instant::now());
+        assert_eq!(usage.circuit_id, 3);
+        assert_eq!(usage.base_proofs_count, 2);
+        assert_eq!(usage.peak_memory_bytes, Some(4096));
+    }
+
+    #[test]
+    fn nothing_is_shed_without_high_priority_circuits() {
+        assert_eq!(circuit_ids_to_claim(11, 10, &[]), None);
+    }
+
+    #[test]
+    fn claimed_circuits_are_restricted_to_allowlist() {
+        assert_eq!(restrict_to_allowlist(None, None), None);
+        assert_eq!(restrict_to_allowlist(Some(&[1, 3]), None), Some(vec![1, 3]));
+        assert_eq!(restrict_to_allowlist(None, Some(&[3, 4])), Some(vec![3, 4]));
+        assert_eq!(
+            restrict_to_allowlist(Some(&[1, 3]), Some(&[3, 4])),
+            Some(vec![3])
+        );
+        let drain_settings = WorkerRole::Drain.settings();
+        assert_eq!(
+            restrict_to_allowlist(None, drain_settings.circuit_ids_allowlist.as_deref()),
+            Some(vec![])
+        );
     }
 }
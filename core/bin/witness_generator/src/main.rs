@@ -1,31 +1,46 @@
 #![feature(generic_const_exprs)]
 
-use prometheus_exporter::run_prometheus_exporter;
-use std::time::Instant;
+use futures::future::BoxFuture;
+use prometheus_exporter::run_prometheus_exporter_with_handle;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
-use tokio::sync::watch;
+use tokio::sync::{oneshot, watch};
 use zksync_config::configs::{AlertsConfig, FriWitnessGeneratorConfig, PrometheusConfig};
 use zksync_config::ObjectStoreConfig;
 use zksync_dal::{connection::DbVariant, ConnectionPool};
+use zksync_health_check::CheckHealth;
 use zksync_object_store::ObjectStoreFactory;
 use zksync_prover_utils::get_stop_signal_receiver;
 use zksync_queued_job_processor::JobProcessor;
 use zksync_types::proofs::AggregationRound;
 use zksync_types::web3::futures::StreamExt;
+use zksync_types::L1BatchNumber;
 use zksync_utils::wait_for_tasks::wait_for_tasks;
 
 use crate::basic_circuits::BasicWitnessGenerator;
+use crate::completion_webhook::CompletionWebhook;
+use crate::health_server::{run_health_server, DatabaseHealthCheck, ObjectStoreHealthCheck};
 use crate::leaf_aggregation::LeafAggregationWitnessGenerator;
+use crate::metrics_sink::{CardinalityGuardSink, MetricsCrateSink, MetricsSink};
 use crate::node_aggregation::NodeAggregationWitnessGenerator;
 use crate::scheduler::SchedulerWitnessGenerator;
 
 mod basic_circuits;
+mod completion_webhook;
+mod error;
+mod health_server;
 mod leaf_aggregation;
+mod metrics_sink;
 mod node_aggregation;
 mod precalculated_merkle_paths_provider;
+mod resource_usage;
 mod scheduler;
 mod utils;
 
+/// Time given to generators to fail the jobs that exceeded the shutdown timeout.
+const SAVE_FAILURE_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "Run witness generator for different aggregation round",
@@ -35,101 +50,288 @@ struct Opt {
     /// Number of times witness generator should be run.
     #[structopt(short = "b", long = "batch_size")]
     batch_size: Option<usize>,
-    /// aggregation round for the witness generator.
+    /// Aggregation round for the witness generator (`basic`, `leaf`, `node` or `scheduler`).
+    /// If not specified, the witness generator runs all rounds.
     #[structopt(short = "r", long = "round")]
+    round: Option<AggregationRound>,
+    /// Only checks that all queued jobs can be prepared, without processing them or changing
+    /// their status. Exits with a nonzero code if any job can't be prepared. Only `leaf` jobs
+    /// are supported.
+    #[structopt(long = "validate-only")]
+    validate_only: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Requeues failed jobs, e.g. once the cause of their failure is fixed.
+    Requeue(RequeueOpt),
+}
+
+#[derive(Debug, StructOpt)]
+struct RequeueOpt {
+    /// Aggregation round of the jobs to requeue. Only `leaf` is supported.
+    #[structopt(long = "round")]
     round: AggregationRound,
+    /// L1 batch number of the jobs to requeue.
+    #[structopt(long = "block", required_unless = "job_id")]
+    block: Option<u32>,
+    /// ID of the job to requeue.
+    #[structopt(long = "job-id")]
+    job_id: Option<u32>,
 }
 
-#[tokio::main]
-async fn main() {
-    vlog::init();
-    let sentry_guard = vlog::init_sentry();
-    match sentry_guard {
-        Some(_) => vlog::info!(
-            "Starting Sentry url: {}",
-            std::env::var("MISC_SENTRY_URL").unwrap(),
-        ),
-        None => vlog::info!("No sentry url configured"),
+impl Opt {
+    fn rounds(&self) -> Vec<AggregationRound> {
+        match self.round {
+            Some(round) => vec![round],
+            None => AggregationRound::ALL.to_vec(),
+        }
     }
+}
 
-    let opt = Opt::from_args();
-    let started_at = Instant::now();
-    vlog::info!(
-        "initializing the {:?} witness generator, batch size: {:?}",
-        opt.round,
-        opt.batch_size
-    );
-    let use_push_gateway = opt.batch_size.is_some();
-
-    let store_factory = ObjectStoreFactory::from_env();
-    let config = FriWitnessGeneratorConfig::from_env();
-    let prometheus_config = PrometheusConfig::from_env();
-    let connection_pool = ConnectionPool::new(None, DbVariant::Master).await;
-    let prover_connection_pool = ConnectionPool::new(None, DbVariant::Prover).await;
-    let (stop_sender, stop_receiver) = watch::channel(false);
+fn run_generator<P: JobProcessor + 'static>(
+    generator: P,
+    stop_receiver: watch::Receiver<bool>,
+    opt: &Opt,
+    max_jobs_before_exit: Option<usize>,
+) -> BoxFuture<'static, ()> {
+    match max_jobs_before_exit {
+        Some(jobs_count) => generator.run_for_jobs(stop_receiver, jobs_count),
+        None => generator.run(stop_receiver, opt.batch_size),
+    }
+}
 
-    let witness_generator_task = match opt.round {
+async fn create_generator_task(
+    round: AggregationRound,
+    config: FriWitnessGeneratorConfig,
+    store_factory: &ObjectStoreFactory,
+    connection_pool: ConnectionPool,
+    prover_connection_pool: ConnectionPool,
+    stop_receiver: watch::Receiver<bool>,
+    opt: &Opt,
+) -> BoxFuture<'static, ()> {
+    let max_jobs_before_exit = config.max_jobs_before_exit;
+    match round {
         AggregationRound::BasicCircuits => {
             let public_blob_store = ObjectStoreFactory::new(ObjectStoreConfig::public_from_env())
                 .create_store()
                 .await;
             let generator = BasicWitnessGenerator::new(
                 config,
-                &store_factory,
+                store_factory,
                 public_blob_store,
                 connection_pool,
                 prover_connection_pool,
             )
             .await;
-            generator.run(stop_receiver, opt.batch_size)
+            run_generator(generator, stop_receiver, opt, max_jobs_before_exit)
         }
         AggregationRound::LeafAggregation => {
+            let metrics_sink: Arc<dyn MetricsSink> = match config.max_metric_cardinality {
+                Some(max_cardinality) => Arc::new(CardinalityGuardSink::new(
+                    MetricsCrateSink,
+                    max_cardinality,
+                    &["circuit_id"],
+                )),
+                None => Arc::new(MetricsCrateSink),
+            };
             let generator = LeafAggregationWitnessGenerator::new(
                 config,
-                &store_factory,
+                store_factory,
                 prover_connection_pool,
+                metrics_sink,
             )
             .await;
-            generator.run(stop_receiver, opt.batch_size)
+            run_generator(generator, stop_receiver, opt, max_jobs_before_exit)
         }
         AggregationRound::NodeAggregation => {
-            let generator =
-                NodeAggregationWitnessGenerator::new(&store_factory, prover_connection_pool).await;
-            generator.run(stop_receiver, opt.batch_size)
+            let available_parallelism =
+                std::thread::available_parallelism().map_or(1, |threads| threads.get());
+            let generator = NodeAggregationWitnessGenerator::new(
+                store_factory,
+                prover_connection_pool,
+                config.reconcile_upstream_artifacts.unwrap_or(false),
+                config.aggregation_threads(available_parallelism),
+                config.worker_settings().circuit_ids_allowlist,
+                config.content_addressed_aggregations.unwrap_or(false),
+                config.witness_generation_timeout(),
+            )
+            .await;
+            run_generator(generator, stop_receiver, opt, max_jobs_before_exit)
         }
         AggregationRound::Scheduler => {
-            let generator =
-                SchedulerWitnessGenerator::new(&store_factory, prover_connection_pool).await;
-            generator.run(stop_receiver, opt.batch_size)
-        }
-    };
-    let tasks = vec![
-        run_prometheus_exporter(
-            prometheus_config.listener_port,
-            use_push_gateway.then(|| {
-                (
-                    prometheus_config.pushgateway_url.clone(),
-                    prometheus_config.push_interval(),
+            let completion_webhook = config.completion_webhook_url.clone().map(|url| {
+                CompletionWebhook::new(
+                    url,
+                    config.completion_webhook_auth_header.clone(),
+                    config.completion_webhook_on_failure.unwrap_or(false),
                 )
-            }),
+            });
+            let generator = SchedulerWitnessGenerator::new(
+                store_factory,
+                prover_connection_pool,
+                completion_webhook,
+                config.witness_generation_timeout(),
+            )
+            .await;
+            run_generator(generator, stop_receiver, opt, max_jobs_before_exit)
+        }
+    }
+}
+
+async fn requeue_failed_jobs(opt: &RequeueOpt) {
+    if opt.round != AggregationRound::LeafAggregation {
+        panic!("requeueing {:?} jobs is not supported", opt.round);
+    }
+    let prover_connection_pool = ConnectionPool::new(Some(1), DbVariant::Prover).await;
+    let mut prover_connection = prover_connection_pool.access_storage().await;
+    let mut transaction = prover_connection.start_transaction().await;
+    let requeued = transaction
+        .fri_witness_generator_dal()
+        .requeue_failed_leaf_aggregation_jobs(opt.block.map(L1BatchNumber), opt.job_id)
+        .await;
+    transaction.commit().await;
+    println!("Requeued {requeued} failed {:?} jobs", opt.round);
+}
+
+/// Returns whether all queued jobs can be prepared.
+async fn validate_queued_jobs(opt: &Opt) -> bool {
+    if let Some(round) = opt
+        .round
+        .filter(|&round| round != AggregationRound::LeafAggregation)
+    {
+        panic!("validating {:?} jobs is not supported", round);
+    }
+    let prover_connection_pool = ConnectionPool::new(Some(1), DbVariant::Prover).await;
+    let generator = LeafAggregationWitnessGenerator::new(
+        FriWitnessGeneratorConfig::from_env(),
+        &ObjectStoreFactory::from_env(),
+        prover_connection_pool,
+        Arc::new(MetricsCrateSink),
+    )
+    .await;
+    let summary = generator.validate_queued_jobs().await;
+    for job in &summary.jobs {
+        let outcome = match &job.result {
+            Ok(()) => "OK",
+            Err(err) => err.as_str(),
+        };
+        println!(
+            "Job {} (block {}, circuit {}): {outcome}",
+            job.job_id, job.block_number, job.circuit_id
+        );
+    }
+    println!(
+        "{} validated / {} missing {:?} jobs",
+        summary.validated,
+        summary.missing,
+        AggregationRound::LeafAggregation
+    );
+    summary.missing == 0
+}
+
+#[tokio::main]
+async fn main() {
+    vlog::init();
+    let sentry_guard = vlog::init_sentry();
+    match sentry_guard {
+        Some(_) => vlog::info!(
+            "Starting Sentry url: {}",
+            std::env::var("MISC_SENTRY_URL").unwrap(),
         ),
-        tokio::spawn(witness_generator_task),
-    ];
+        None => vlog::info!("No sentry url configured"),
+    }
+
+    let opt = Opt::from_args();
+    if let Some(Command::Requeue(requeue_opt)) = &opt.command {
+        requeue_failed_jobs(requeue_opt).await;
+        return;
+    }
+    if opt.validate_only {
+        if !validate_queued_jobs(&opt).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let started_at = Instant::now();
+    let rounds = opt.rounds();
+    vlog::info!(
+        "initializing the {:?} witness generator, batch size: {:?}",
+        rounds,
+        opt.batch_size
+    );
+    let store_factory = ObjectStoreFactory::from_env();
+    let config = FriWitnessGeneratorConfig::from_env();
+    let max_jobs_before_exit = config.max_jobs_before_exit;
+    let shutdown_timeout = config.witness_generation_timeout();
+    let use_push_gateway = opt.batch_size.is_some() || max_jobs_before_exit.is_some();
+    let prometheus_config = PrometheusConfig::from_env();
+    let connection_pool = ConnectionPool::new(None, DbVariant::Master).await;
+    let prover_connection_pool = ConnectionPool::new(None, DbVariant::Prover).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+
+    let mut generator_tasks = Vec::with_capacity(rounds.len());
+    for &round in &rounds {
+        let task = create_generator_task(
+            round,
+            config.clone(),
+            &store_factory,
+            connection_pool.clone(),
+            prover_connection_pool.clone(),
+            stop_receiver.clone(),
+            &opt,
+        )
+        .await;
+        generator_tasks.push(task);
+    }
+    let (prometheus_task, metrics_flusher, metrics_handle) = run_prometheus_exporter_with_handle(
+        prometheus_config.listener_port,
+        use_push_gateway.then(|| {
+            (
+                prometheus_config.pushgateway_url.clone(),
+                prometheus_config.push_interval(),
+            )
+        }),
+    );
+    let (generator_finished_sender, generator_finished_receiver) = oneshot::channel();
+    let witness_generator_task = tokio::spawn(async move {
+        futures::future::join_all(generator_tasks).await;
+        generator_finished_sender.send(()).ok();
+    });
+    let mut tasks = vec![prometheus_task, witness_generator_task];
+    if let Some(port) = config.health_check_port {
+        let healthchecks: Vec<Box<dyn CheckHealth>> = vec![
+            Box::new(DatabaseHealthCheck::new(prover_connection_pool.clone())),
+            Box::new(ObjectStoreHealthCheck::new(
+                store_factory.create_store().await,
+            )),
+        ];
+        tasks.push(run_health_server(
+            ([0, 0, 0, 0], port).into(),
+            healthchecks,
+            metrics_handle,
+            stop_receiver.clone(),
+        ));
+    }
     vlog::info!(
         "initialized {:?} witness generator in {:?}",
-        opt.round,
+        rounds,
         started_at.elapsed()
     );
-    metrics::gauge!(
-        "server.init.latency",
-        started_at.elapsed(),
-        "stage" => format!("fri_witness_generator_{:?}", opt.round)
-    );
+    for round in &rounds {
+        metrics::gauge!(
+            "server.init.latency",
+            started_at.elapsed(),
+            "stage" => format!("fri_witness_generator_{:?}", round)
+        );
+    }
 
     let mut stop_signal_receiver = get_stop_signal_receiver();
     let particular_crypto_alerts = Some(AlertsConfig::from_env().sporadic_crypto_errors_substrs);
     let graceful_shutdown = None::<futures::future::Ready<()>>;
-    let tasks_allowed_to_finish = false;
+    let tasks_allowed_to_finish = max_jobs_before_exit.is_some();
     tokio::select! {
         _ = wait_for_tasks(tasks, particular_crypto_alerts, graceful_shutdown, tasks_allowed_to_finish) => {},
         _ = stop_signal_receiver.next() => {
@@ -138,5 +340,20 @@ async fn main() {
     }
 
     stop_sender.send(true).ok();
+    // Let the job being processed finish and save its result; generators fail the job
+    // with the "shutdown" error once it exceeds `shutdown_timeout`, so that it's not left in progress.
+    let shutdown_timeout = shutdown_timeout + SAVE_FAILURE_TIMEOUT;
+    if tokio::time::timeout(shutdown_timeout, generator_finished_receiver)
+        .await
+        .is_err()
+    {
+        vlog::warn!("Witness generator didn't finish its job within {shutdown_timeout:?}");
+    }
+    // Metrics recorded since the last periodic push would otherwise be lost.
+    if let Some(flusher) = metrics_flusher {
+        if let Err(err) = flusher.flush().await {
+            vlog::warn!("Failed to flush metrics to the Pushgateway: {err}");
+        }
+    }
     vlog::info!("Finished witness generation");
 }
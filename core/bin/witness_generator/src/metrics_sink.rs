@@ -0,0 +1,180 @@
+//! Abstraction over the metrics backend, so that instrumentation isn't tied to the `metrics` crate.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+
+use metrics::Label;
+
+/// Labels attached to a single metric emission.
+pub type MetricLabels<'a> = &'a [(&'static str, String)];
+
+/// Destination of the metrics emitted by witness generators.
+pub trait MetricsSink: fmt::Debug + Send + Sync {
+    fn increment_counter(&self, name: &'static str, value: u64, labels: MetricLabels<'_>);
+    fn set_gauge(&self, name: &'static str, value: f64, labels: MetricLabels<'_>);
+    fn record_histogram(&self, name: &'static str, value: f64, labels: MetricLabels<'_>);
+}
+
+/// Default sink reporting to the recorder installed for the `metrics` crate.
+#[derive(Debug, Default)]
+pub struct MetricsCrateSink;
+
+fn to_labels(labels: MetricLabels<'_>) -> Vec<Label> {
+    labels
+        .iter()
+        .map(|(key, value)| Label::new(*key, value.clone()))
+        .collect()
+}
+
+impl MetricsSink for MetricsCrateSink {
+    fn increment_counter(&self, name: &'static str, value: u64, labels: MetricLabels<'_>) {
+        metrics::counter!(name, value, to_labels(labels));
+    }
+
+    fn set_gauge(&self, name: &'static str, value: f64, labels: MetricLabels<'_>) {
+        metrics::gauge!(name, value, to_labels(labels));
+    }
+
+    fn record_histogram(&self, name: &'static str, value: f64, labels: MetricLabels<'_>) {
+        metrics::histogram!(name, value, to_labels(labels));
+    }
+}
+
+/// Label value that high-cardinality label values are collapsed into.
+const OTHER_LABEL_VALUE: &str = "other";
+
+type LabelSet = Vec<(&'static str, String)>;
+
+/// Guards the metrics backend against cardinality explosion: once a metric has been emitted with
+/// `max_cardinality` distinct label combinations, values of `collapsed_labels` in new combinations
+/// are replaced with `"other"`.
+#[derive(Debug)]
+pub struct CardinalityGuardSink<S> {
+    inner: S,
+    max_cardinality: usize,
+    collapsed_labels: &'static [&'static str],
+    seen_label_sets: Mutex<HashMap<&'static str, HashSet<LabelSet>>>,
+}
+
+impl<S: MetricsSink> CardinalityGuardSink<S> {
+    pub fn new(
+        inner: S,
+        max_cardinality: usize,
+        collapsed_labels: &'static [&'static str],
+    ) -> Self {
+        Self {
+            inner,
+            max_cardinality,
+            collapsed_labels,
+            seen_label_sets: Mutex::default(),
+        }
+    }
+
+    fn guard(&self, name: &'static str, labels: MetricLabels<'_>) -> LabelSet {
+        let mut seen_label_sets = self.seen_label_sets.lock().unwrap();
+        let seen = seen_label_sets.entry(name).or_default();
+        let labels = labels.to_vec();
+        if seen.contains(&labels) {
+            return labels;
+        }
+        if seen.len() < self.max_cardinality {
+            seen.insert(labels.clone());
+            return labels;
+        }
+
+        let collapsed: LabelSet = labels
+            .into_iter()
+            .map(|(key, value)| {
+                if self.collapsed_labels.contains(&key) {
+                    (key, OTHER_LABEL_VALUE.to_owned())
+                } else {
+                    (key, value)
+                }
+            })
+            .collect();
+        if seen.insert(collapsed.clone()) {
+            vlog::warn!(
+                "Metric `{name}` exceeded {} label combinations; collapsing labels {:?} into `{OTHER_LABEL_VALUE}`",
+                self.max_cardinality,
+                self.collapsed_labels
+            );
+        }
+        collapsed
+    }
+}
+
+impl<S: MetricsSink> MetricsSink for CardinalityGuardSink<S> {
+    fn increment_counter(&self, name: &'static str, value: u64, labels: MetricLabels<'_>) {
+        let labels = self.guard(name, labels);
+        self.inner.increment_counter(name, value, &labels);
+    }
+
+    fn set_gauge(&self, name: &'static str, value: f64, labels: MetricLabels<'_>) {
+        let labels = self.guard(name, labels);
+        self.inner.set_gauge(name, value, &labels);
+    }
+
+    fn record_histogram(&self, name: &'static str, value: f64, labels: MetricLabels<'_>) {
+        let labels = self.guard(name, labels);
+        self.inner.record_histogram(name, value, &labels);
+    }
+}
+
+/// Sink recording all emitted metrics as `(name, value, labels)`.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct RecordingSink {
+    pub metrics: Mutex<Vec<(&'static str, f64, LabelSet)>>,
+}
+
+#[cfg(test)]
+impl MetricsSink for RecordingSink {
+    fn increment_counter(&self, name: &'static str, value: u64, labels: MetricLabels<'_>) {
+        self.record_histogram(name, value as f64, labels);
+    }
+
+    fn set_gauge(&self, name: &'static str, value: f64, labels: MetricLabels<'_>) {
+        self.record_histogram(name, value, labels);
+    }
+
+    fn record_histogram(&self, name: &'static str, value: f64, labels: MetricLabels<'_>) {
+        self.metrics
+            .lock()
+            .unwrap()
+            .push((name, value, labels.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_values_beyond_limit_are_collapsed() {
+        let sink = CardinalityGuardSink::new(RecordingSink::default(), 2, &["circuit_id"]);
+        for circuit_id in [1, 2, 3, 1, 4] {
+            let labels = [
+                ("circuit_id", circuit_id.to_string()),
+                ("aggregation_round", "LeafAggregation".to_owned()),
+            ];
+            sink.increment_counter("jobs_processed", 1, &labels);
+        }
+
+        let circuit_ids: Vec<_> = sink
+            .inner
+            .metrics
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, labels)| {
+                assert_eq!(
+                    labels[1],
+                    ("aggregation_round", "LeafAggregation".to_owned())
+                );
+                labels[0].1.clone()
+            })
+            .collect();
+        assert_eq!(circuit_ids, ["1", "2", "other", "1", "other"]);
+    }
+}
@@ -1,30 +1,45 @@
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use circuit_definitions::boojum::field::goldilocks::GoldilocksField;
 use circuit_definitions::circuit_definitions::recursion_layer::{
     ZkSyncRecursionLayerProof, ZkSyncRecursionLayerStorageType,
-    ZkSyncRecursionLayerVerificationKey, ZkSyncRecursiveLayerCircuit,
+    ZkSyncRecursionLayerVerificationKey, ZkSyncRecursiveLayerCircuit, RECURSION_ARITY,
 };
 use circuit_definitions::encodings::recursion_request::RecursionQueueSimulator;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
 use zkevm_test_harness::witness::recursive_aggregation::{
     compute_node_vk_commitment, create_node_witnesses,
 };
 use zkevm_test_harness::zkevm_circuits::recursion::leaf_layer::input::RecursionLeafParametersWitness;
+use zkevm_test_harness::zkevm_circuits::recursion::VK_COMMITMENT_LENGTH;
 use zksync_vk_setup_data_server_fri::get_recursive_layer_vk_for_circuit_type;
 use zksync_vk_setup_data_server_fri::utils::get_leaf_vk_params;
 
+use crate::leaf_aggregation::validate_recursion_queues;
+use crate::resource_usage::{peak_memory_bytes, resident_memory_bytes};
 use crate::utils::{
+    get_base_layer_circuit_id_for_recursive_layer, get_recursive_layer_circuit_id_for_base_layer,
     load_proofs_for_job_ids, save_node_aggregations_artifacts,
-    save_recursive_layer_prover_input_artifacts, AggregationWrapper, FriProofWrapper,
+    save_recursive_layer_prover_input_artifacts, time_in_queue, AggregationWrapper,
+    FriProofWrapper,
 };
+use zksync_config::configs::object_store::ContentHashAlgorithm;
 use zksync_dal::ConnectionPool;
-use zksync_object_store::{AggregationsKey, ObjectStore, ObjectStoreFactory};
+use zksync_object_store::{AggregationsKey, ObjectStore, ObjectStoreError, ObjectStoreFactory};
 use zksync_queued_job_processor::JobProcessor;
-use zksync_types::proofs::NodeAggregationJobMetadata;
+use zksync_types::proofs::{BaseLayerCircuitId, NodeAggregationJobMetadata};
 use zksync_types::{proofs::AggregationRound, L1BatchNumber};
 
+type NodeAggregations = Vec<(
+    u64,
+    RecursionQueueSimulator<GoldilocksField>,
+    ZkSyncRecursiveLayerCircuit,
+)>;
+
 pub struct NodeAggregationArtifacts {
     circuit_id: u8,
     block_number: L1BatchNumber,
@@ -62,23 +77,83 @@ pub struct NodeAggregationWitnessGeneratorJob {
 pub struct NodeAggregationWitnessGenerator {
     object_store: Box<dyn ObjectStore>,
     prover_connection_pool: ConnectionPool,
+    reconcile_upstream_artifacts: bool,
+    thread_pool: Arc<ThreadPool>,
+    /// Recursive layer circuit ids to pick jobs for; `None` means all circuits.
+    circuit_ids_allowlist: Option<Vec<u8>>,
+    /// Algorithm of content hashes in the keys of saved aggregations, if they're content-addressed.
+    content_hash_algorithm: Option<ContentHashAlgorithm>,
+    shutdown_timeout: Duration,
 }
 
 impl NodeAggregationWitnessGenerator {
     pub async fn new(
         store_factory: &ObjectStoreFactory,
         prover_connection_pool: ConnectionPool,
+        reconcile_upstream_artifacts: bool,
+        aggregation_threads: usize,
+        base_circuit_ids_allowlist: Option<Vec<u8>>,
+        content_addressed_aggregations: bool,
+        shutdown_timeout: Duration,
     ) -> Self {
+        let thread_pool = ThreadPoolBuilder::new()
+            .num_threads(aggregation_threads)
+            .thread_name(|idx| format!("node-aggregation-{idx}"))
+            .build()
+            .expect("failed building node aggregation thread pool");
         Self {
-            object_store: store_factory.create_store().await,
+            object_store: store_factory
+                .create_store_for_round(AggregationRound::NodeAggregation)
+                .await,
             prover_connection_pool,
+            reconcile_upstream_artifacts,
+            thread_pool: Arc::new(thread_pool),
+            content_hash_algorithm: content_addressed_aggregations
+                .then(|| store_factory.content_hash_algorithm()),
+            shutdown_timeout,
+            circuit_ids_allowlist: base_circuit_ids_allowlist.map(|circuit_ids| {
+                circuit_ids
+                    .into_iter()
+                    .map(|id| {
+                        get_recursive_layer_circuit_id_for_base_layer(BaseLayerCircuitId(id)).0
+                    })
+                    .collect()
+            }),
         }
     }
 
+    /// Handles a depth-0 job whose aggregations, saved by the leaf aggregation job of the circuit,
+    /// are missing even though the leaf aggregation job is successful (e.g., because the blob
+    /// was deleted). The leaf aggregation job is requeued to recreate them, and the job waits for it.
+    async fn reconcile_missing_leaf_artifacts(&self, metadata: &NodeAggregationJobMetadata) {
+        metrics::increment_counter!(
+            "prover_fri.witness_generation.state_inconsistency",
+            "aggregation_round" => format!("{:?}", AggregationRound::LeafAggregation),
+            "circuit_id" => metadata.circuit_id.to_string(),
+        );
+        let requeued = self
+            .prover_connection_pool
+            .access_storage()
+            .await
+            .fri_witness_generator_dal()
+            .requeue_leaf_aggregation_job_with_missing_artifacts(
+                metadata.block_number,
+                get_base_layer_circuit_id_for_recursive_layer(metadata.circuit_id),
+                metadata.id,
+            )
+            .await;
+        vlog::warn!(
+            "Leaf aggregation artifacts for node aggregation job {} are missing; leaf aggregation job requeued: {requeued}",
+            metadata.id
+        );
+    }
+
     fn process_job_sync(
         job: NodeAggregationWitnessGeneratorJob,
         started_at: Instant,
+        thread_pool: &ThreadPool,
     ) -> NodeAggregationArtifacts {
+        let memory_before = resident_memory_bytes();
         let node_vk_commitment = compute_node_vk_commitment(job.node_vk.clone());
         vlog::info!(
             "Starting witness generation of type {:?} for block {} circuit id {} depth {}",
@@ -91,18 +166,30 @@ impl NodeAggregationWitnessGenerator {
             0 => job.leaf_vk,
             _ => job.node_vk,
         };
-        let next_aggregations = create_node_witnesses(
+        let next_aggregations = create_node_witnesses_in_parallel(
+            thread_pool,
             job.aggregations,
             job.proofs,
             vk,
             node_vk_commitment,
             &job.all_leafs_layer_params,
         );
+        validate_recursion_queues(next_aggregations.iter().map(|(_, queue, _)| queue))
+            .unwrap_or_else(|err| panic!("{}", err));
         metrics::histogram!(
                     "prover_fri.witness_generation.witness_generation_time",
                     started_at.elapsed(),
                     "aggregation_round" => format!("{:?}", AggregationRound::NodeAggregation),
+                    "circuit_id" => job.circuit_id.to_string(),
         );
+        if let Some(peak_memory_bytes) = peak_memory_bytes(memory_before) {
+            metrics::histogram!(
+                "prover_fri.witness_generation.peak_memory_bytes",
+                peak_memory_bytes as f64,
+                "aggregation_round" => format!("{:?}", AggregationRound::NodeAggregation),
+                "circuit_id" => job.circuit_id.to_string(),
+            );
+        }
         vlog::info!(
         "Node witness generation for block {} with circuit id {} at depth {} with {} next_aggregations jobs completed in {:?}.",
         job.block_number.0,
@@ -133,13 +220,35 @@ impl JobProcessor for NodeAggregationWitnessGenerator {
         let mut prover_connection = self.prover_connection_pool.access_storage().await;
         let metadata = prover_connection
             .fri_witness_generator_dal()
-            .get_next_node_aggregation_job()
+            .get_next_node_aggregation_job(self.circuit_ids_allowlist.as_deref())
             .await?;
+        metrics::histogram!(
+            "prover_fri.witness_generation.time_in_queue",
+            time_in_queue(metadata.created_at),
+            "aggregation_round" => format!("{:?}", AggregationRound::NodeAggregation),
+        );
+        if let Some(latency) = prover_connection
+            .fri_witness_generator_dal()
+            .get_round_handoff_latency(metadata.block_number)
+            .await
+        {
+            metrics::histogram!("prover_fri.round_handoff_latency_seconds", latency);
+        }
         vlog::info!("Processing node aggregation job {:?}", metadata.id);
-        Some((
-            metadata.id,
-            prepare_job(metadata, &*self.object_store).await,
-        ))
+        match prepare_job(&metadata, &*self.object_store).await {
+            Ok(job) => Some((metadata.id, job)),
+            Err(ObjectStoreError::KeyNotFound(_))
+                if self.reconcile_upstream_artifacts && metadata.depth == 0 =>
+            {
+                drop(prover_connection);
+                self.reconcile_missing_leaf_artifacts(&metadata).await;
+                None
+            }
+            Err(err) => panic!(
+                "node aggregation job artifacts missing for job {}: {err}",
+                metadata.id
+            ),
+        }
     }
 
     async fn save_failure(&self, job_id: u32, _started_at: Instant, error: String) -> () {
@@ -157,7 +266,8 @@ impl JobProcessor for NodeAggregationWitnessGenerator {
         job: NodeAggregationWitnessGeneratorJob,
         started_at: Instant,
     ) -> tokio::task::JoinHandle<NodeAggregationArtifacts> {
-        tokio::task::spawn_blocking(move || Self::process_job_sync(job, started_at))
+        let thread_pool = self.thread_pool.clone();
+        tokio::task::spawn_blocking(move || Self::process_job_sync(job, started_at, &thread_pool))
     }
 
     async fn save_result(
@@ -170,7 +280,8 @@ impl JobProcessor for NodeAggregationWitnessGenerator {
         let circuit_id = artifacts.circuit_id;
         let depth = artifacts.depth;
         let shall_continue_node_aggregations = artifacts.next_aggregations.len() > 1;
-        let blob_urls = save_artifacts(artifacts, &*self.object_store).await;
+        let blob_urls =
+            save_artifacts(artifacts, &*self.object_store, self.content_hash_algorithm).await;
         update_database(
             &self.prover_connection_pool,
             started_at,
@@ -183,19 +294,105 @@ impl JobProcessor for NodeAggregationWitnessGenerator {
         )
         .await;
     }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        Some(self.shutdown_timeout)
+    }
+}
+
+/// Returns the number of aggregations processed by a single thread when creating node witnesses
+/// with `threads` threads. `create_node_witnesses` aggregates every `RECURSION_ARITY` consecutive
+/// aggregations into a node circuit independently of the other ones, so chunks are aligned to the arity.
+fn node_aggregations_chunk_size(aggregations_count: usize, threads: usize) -> usize {
+    let threads = threads.max(1);
+    let node_circuits_count = (aggregations_count + RECURSION_ARITY - 1) / RECURSION_ARITY;
+    let node_circuits_per_thread = (node_circuits_count + threads - 1) / threads;
+    node_circuits_per_thread.max(1) * RECURSION_ARITY
+}
+
+/// Creates node witnesses on `thread_pool`, splitting the aggregations into chunks processed in parallel.
+/// The output is ordered as if the aggregations were processed by a single `create_node_witnesses` call.
+#[allow(clippy::ptr_arg)] // `create_node_witnesses` takes the params by a `Vec` reference
+fn create_node_witnesses_in_parallel(
+    thread_pool: &ThreadPool,
+    aggregations: NodeAggregations,
+    proofs: Vec<ZkSyncRecursionLayerProof>,
+    vk: ZkSyncRecursionLayerVerificationKey,
+    node_vk_commitment: [GoldilocksField; VK_COMMITMENT_LENGTH],
+    all_leafs_layer_params: &Vec<(u8, RecursionLeafParametersWitness<GoldilocksField>)>,
+) -> NodeAggregations {
+    in_aligned_chunks(thread_pool, aggregations, proofs, |aggregations, proofs| {
+        create_node_witnesses(
+            aggregations,
+            proofs,
+            vk.clone(),
+            node_vk_commitment,
+            all_leafs_layer_params,
+        )
+    })
+}
+
+/// Applies `create_witnesses` to chunks of the aggregations (and the matching proofs) aligned
+/// to `RECURSION_ARITY` on `thread_pool`, concatenating the outputs in the order of the chunks.
+fn in_aligned_chunks<A, P, F>(
+    thread_pool: &ThreadPool,
+    aggregations: Vec<A>,
+    proofs: Vec<P>,
+    create_witnesses: F,
+) -> Vec<A>
+where
+    A: Send,
+    P: Send,
+    F: Fn(Vec<A>, Vec<P>) -> Vec<A> + Sync,
+{
+    let chunk_size =
+        node_aggregations_chunk_size(aggregations.len(), thread_pool.current_num_threads());
+    // Proofs are consumed one per aggregation; chunks can only be split if they match.
+    if aggregations.len() <= chunk_size || aggregations.len() != proofs.len() {
+        return create_witnesses(aggregations, proofs);
+    }
+
+    let mut aggregations = aggregations.into_iter();
+    let mut proofs = proofs.into_iter();
+    let mut chunks = vec![];
+    loop {
+        let aggregations_chunk: Vec<_> = (&mut aggregations).take(chunk_size).collect();
+        if aggregations_chunk.is_empty() {
+            break;
+        }
+        let proofs_chunk: Vec<_> = (&mut proofs).take(chunk_size).collect();
+        chunks.push((aggregations_chunk, proofs_chunk));
+    }
+    // Collecting an indexed parallel iterator preserves the order of chunks.
+    let results: Vec<_> = thread_pool.install(|| {
+        chunks
+            .into_par_iter()
+            .map(|(aggregations, proofs)| create_witnesses(aggregations, proofs))
+            .collect()
+    });
+    results.into_iter().flatten().collect()
 }
 
 async fn prepare_job(
-    metadata: NodeAggregationJobMetadata,
+    metadata: &NodeAggregationJobMetadata,
     object_store: &dyn ObjectStore,
-) -> NodeAggregationWitnessGeneratorJob {
+) -> Result<NodeAggregationWitnessGeneratorJob, ObjectStoreError> {
     let started_at = Instant::now();
-    let artifacts = get_artifacts(&metadata, object_store).await;
-    let proofs = load_proofs_for_job_ids(&metadata.prover_job_ids_for_proofs, object_store).await;
+    let artifacts = get_artifacts(metadata, object_store).await?;
+    let proofs = load_proofs_for_job_ids(
+        &metadata.prover_job_ids_for_proofs,
+        object_store,
+        AggregationRound::NodeAggregation,
+        1,
+        false,
+    )
+    .await
+    .unwrap_or_else(|err| panic!("failed loading proofs: {err}"));
     metrics::histogram!(
                     "prover_fri.witness_generation.blob_fetch_time",
                     started_at.elapsed(),
                     "aggregation_round" => format!("{:?}", AggregationRound::NodeAggregation),
+                    "circuit_id" => metadata.circuit_id.to_string(),
     );
     let started_at = Instant::now();
     let leaf_vk = get_recursive_layer_vk_for_circuit_type(metadata.circuit_id);
@@ -220,8 +417,9 @@ async fn prepare_job(
                     "prover_fri.witness_generation.job_preparation_time",
                     started_at.elapsed(),
                     "aggregation_round" => format!("{:?}", AggregationRound::NodeAggregation),
+                    "circuit_id" => metadata.circuit_id.to_string(),
     );
-    NodeAggregationWitnessGeneratorJob {
+    Ok(NodeAggregationWitnessGeneratorJob {
         circuit_id: metadata.circuit_id,
         block_number: metadata.block_number,
         depth: metadata.depth,
@@ -230,7 +428,7 @@ async fn prepare_job(
         leaf_vk,
         node_vk,
         all_leafs_layer_params: get_leaf_vk_params(),
-    }
+    })
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -268,6 +466,10 @@ async fn update_database(
                     &blob_urls.node_aggregations_url,
                 )
                 .await;
+            transaction
+                .fri_witness_generator_dal()
+                .insert_node_aggregation_job_dependencies(block_number, circuit_id, depth)
+                .await;
         }
         false => {
             let (_, blob_url) = blob_urls.circuit_ids_and_urls[0].clone();
@@ -297,29 +499,34 @@ async fn update_database(
 async fn get_artifacts(
     metadata: &NodeAggregationJobMetadata,
     object_store: &dyn ObjectStore,
-) -> AggregationWrapper {
+) -> Result<AggregationWrapper, ObjectStoreError> {
+    if let Some(url) = &metadata.aggregations_url {
+        return object_store.get_by_encoded_key(url).await;
+    }
+    // Jobs created before the URL was recorded load aggregations by their logical key.
     let key = AggregationsKey {
         block_number: metadata.block_number,
         circuit_id: metadata.circuit_id,
         depth: metadata.depth,
     };
-    object_store
-        .get(key)
-        .await
-        .unwrap_or_else(|_| panic!("node aggregation job artifacts missing: {:?}", key))
+    object_store.get(key).await
 }
 
 async fn save_artifacts(
     artifacts: NodeAggregationArtifacts,
     object_store: &dyn ObjectStore,
+    content_hash_algorithm: Option<ContentHashAlgorithm>,
 ) -> BlobUrls {
     let started_at = Instant::now();
+    let circuit_id = artifacts.circuit_id;
     let aggregations_urls = save_node_aggregations_artifacts(
         artifacts.block_number,
-        artifacts.circuit_id,
+        circuit_id,
         artifacts.depth,
         artifacts.next_aggregations.clone(),
+        AggregationRound::NodeAggregation,
         object_store,
+        content_hash_algorithm,
     )
     .await;
     let circuit_ids_and_urls = save_recursive_layer_prover_input_artifacts(
@@ -328,16 +535,108 @@ async fn save_artifacts(
         AggregationRound::NodeAggregation,
         artifacts.depth,
         object_store,
-        Some(artifacts.circuit_id),
+        Some(circuit_id),
+        1,
     )
     .await;
     metrics::histogram!(
                     "prover_fri.witness_generation.blob_save_time",
                     started_at.elapsed(),
                     "aggregation_round" => format!("{:?}", AggregationRound::NodeAggregation),
+                    "circuit_id" => circuit_id.to_string(),
     );
     BlobUrls {
         node_aggregations_url: aggregations_urls,
         circuit_ids_and_urls,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_aggregations_chunks_are_aligned_to_arity() {
+        assert_eq!(node_aggregations_chunk_size(1, 4), RECURSION_ARITY);
+        assert_eq!(
+            node_aggregations_chunk_size(RECURSION_ARITY * 8, 4),
+            RECURSION_ARITY * 2
+        );
+        assert_eq!(
+            node_aggregations_chunk_size(RECURSION_ARITY * 3 + 1, 2),
+            RECURSION_ARITY * 2
+        );
+        assert_eq!(
+            node_aggregations_chunk_size(RECURSION_ARITY * 3, 1),
+            RECURSION_ARITY * 3
+        );
+        assert_eq!(
+            node_aggregations_chunk_size(RECURSION_ARITY * 3, 8),
+            RECURSION_ARITY
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_leaf_artifacts_are_reported_as_not_found() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let metadata = NodeAggregationJobMetadata {
+            id: 1,
+            block_number: L1BatchNumber(1),
+            circuit_id: 3,
+            depth: 0,
+            prover_job_ids_for_proofs: vec![1],
+            aggregations_url: None,
+            created_at: chrono::Utc::now(),
+        };
+        // Lets the job requeue the leaf aggregation job rather than fail on the missing blob.
+        let err = prepare_job(&metadata, &*object_store).await.err().unwrap();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)));
+    }
+
+    /// Stand-in for `create_node_witnesses` with the same contract: every `RECURSION_ARITY`
+    /// consecutive aggregations are aggregated with their proofs into a single output,
+    /// independently of the other ones.
+    fn aggregate_by_arity(aggregations: Vec<u64>, proofs: Vec<u64>) -> Vec<u64> {
+        aggregations
+            .chunks(RECURSION_ARITY)
+            .zip(proofs.chunks(RECURSION_ARITY))
+            .map(|(aggregations, proofs)| {
+                aggregations
+                    .iter()
+                    .zip(proofs)
+                    .map(|(aggregation, proof)| aggregation * proof)
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_node_witnesses_match_single_call() {
+        let arity = RECURSION_ARITY;
+        for threads in [1, 2, 3, 8] {
+            let thread_pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            for count in [1, arity - 1, arity, arity + 1, arity * 3, arity * 7 + 2] {
+                let aggregations: Vec<u64> = (1..=count as u64).collect();
+                let proofs: Vec<u64> = aggregations.iter().map(|id| id * 7 % 11).collect();
+                let expected = aggregate_by_arity(aggregations.clone(), proofs.clone());
+
+                let actual =
+                    in_aligned_chunks(&thread_pool, aggregations, proofs, aggregate_by_arity);
+                assert_eq!(
+                    actual.len(),
+                    expected.len(),
+                    "{threads} threads, {count} aggregations"
+                );
+                for (idx, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+                    assert_eq!(
+                        actual, expected,
+                        "output {idx}, {threads} threads, {count} aggregations"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Opt-in export of per-job resource usage to a CSV file for offline capacity modeling,
+//! and best-effort sampling of the memory used by jobs.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const HEADER: &str =
+    "circuit_id,input_count,base_proofs_count,duration_ms,peak_memory_bytes,artifact_bytes";
+
+/// Resources used by a single completed job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobResourceUsage {
+    pub circuit_id: u8,
+    pub input_count: usize,
+    pub base_proofs_count: usize,
+    pub duration: Duration,
+    /// `None` if memory usage wasn't sampled for the job.
+    pub peak_memory_bytes: Option<u64>,
+    pub artifact_bytes: u64,
+}
+
+impl JobResourceUsage {
+    fn to_csv_row(&self) -> String {
+        let peak_memory_bytes = self
+            .peak_memory_bytes
+            .map(|bytes| bytes.to_string())
+            .unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{}",
+            self.circuit_id,
+            self.input_count,
+            self.base_proofs_count,
+            self.duration.as_millis(),
+            peak_memory_bytes,
+            self.artifact_bytes
+        )
+    }
+}
+
+/// Appends one row per job to a CSV file. Once the file grows over `max_file_bytes`, it is rotated
+/// to `{path}.1` (replacing the previously rotated file) and a fresh file with a header is started.
+#[derive(Debug)]
+pub struct ResourceUsageCsvWriter {
+    path: PathBuf,
+    max_file_bytes: Option<u64>,
+}
+
+impl ResourceUsageCsvWriter {
+    pub fn new(path: impl Into<PathBuf>, max_file_bytes: Option<u64>) -> Self {
+        Self {
+            path: path.into(),
+            max_file_bytes,
+        }
+    }
+
+    pub fn append(&self, usage: &JobResourceUsage) -> io::Result<()> {
+        let current_len = file_len(&self.path)?;
+        let current_len = match self.max_file_bytes {
+            Some(max_file_bytes) if current_len >= max_file_bytes => {
+                fs::rename(&self.path, self.rotated_path())?;
+                0
+            }
+            _ => current_len,
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        if current_len == 0 {
+            writeln!(file, "{HEADER}")?;
+        }
+        writeln!(file, "{}", usage.to_csv_row())
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut rotated_path = self.path.clone().into_os_string();
+        rotated_path.push(".1");
+        rotated_path.into()
+    }
+}
+
+/// Samples the resident set size of the process. Returns `None` on platforms other than Linux
+/// or if `/proc/self/status` can't be read.
+pub fn resident_memory_bytes() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        parse_vm_rss(&status)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Returns the larger of the resident set size sampled before processing a job and the current one.
+/// Memory freed mid-job isn't observed, so this is a lower bound of the actual peak.
+pub fn peak_memory_bytes(sampled_before: Option<u64>) -> Option<u64> {
+    sampled_before.max(resident_memory_bytes())
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+fn file_len(path: &Path) -> io::Result<u64> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn usage(circuit_id: u8) -> JobResourceUsage {
+        JobResourceUsage {
+            circuit_id,
+            input_count: 10,
+            base_proofs_count: 10,
+            duration: Duration::from_millis(1500),
+            peak_memory_bytes: None,
+            artifact_bytes: 2048,
+        }
+    }
+
+    #[test]
+    fn appended_rows_are_read_back() {
+        let dir = TempDir::new("resource-usage").unwrap();
+        let path = dir.path().join("usage.csv");
+        let writer = ResourceUsageCsvWriter::new(&path, None);
+        writer.append(&usage(1)).unwrap();
+        writer
+            .append(&JobResourceUsage {
+                peak_memory_bytes: Some(4096),
+                ..usage(3)
+            })
+            .unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(
+            lines,
+            [HEADER, "1,10,10,1500,,2048", "3,10,10,1500,4096,2048"]
+        );
+    }
+
+    #[test]
+    fn resident_memory_is_parsed_from_proc_status() {
+        let status = "Name:\twitness_generator\nVmHWM:\t  204800 kB\nVmRSS:\t  102400 kB\n";
+        assert_eq!(parse_vm_rss(status), Some(100 * 1024 * 1024));
+        assert_eq!(parse_vm_rss("Name:\twitness_generator\n"), None);
+        assert_eq!(peak_memory_bytes(Some(u64::MAX)), Some(u64::MAX));
+    }
+
+    #[test]
+    fn file_is_rotated_once_over_limit() {
+        let dir = TempDir::new("resource-usage").unwrap();
+        let path = dir.path().join("usage.csv");
+        let writer = ResourceUsageCsvWriter::new(&path, Some(1));
+        writer.append(&usage(1)).unwrap();
+        writer.append(&usage(2)).unwrap();
+
+        let rotated = fs::read_to_string(dir.path().join("usage.csv.1")).unwrap();
+        assert_eq!(
+            rotated.lines().collect::<Vec<_>>(),
+            [HEADER, "1,10,10,1500,,2048"]
+        );
+        let current = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            current.lines().collect::<Vec<_>>(),
+            [HEADER, "2,10,10,1500,,2048"]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::convert::TryInto;
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use circuit_definitions::boojum::field::goldilocks::{GoldilocksExt2, GoldilocksField};
@@ -16,8 +16,10 @@ use circuit_definitions::zkevm_circuits::scheduler::SchedulerConfig;
 use zksync_vk_setup_data_server_fri::get_recursive_layer_vk_for_circuit_type;
 use zksync_vk_setup_data_server_fri::utils::get_leaf_vk_params;
 
+use crate::completion_webhook::{BatchCompletionPayload, BatchCompletionStatus, CompletionWebhook};
 use crate::utils::{
-    load_proofs_for_job_ids, CircuitWrapper, FriProofWrapper, SchedulerPartialInputWrapper,
+    load_proofs_for_job_ids, time_in_queue, CircuitWrapper, FriProofWrapper,
+    SchedulerPartialInputWrapper,
 };
 use zksync_dal::ConnectionPool;
 use zksync_object_store::{FriCircuitKey, ObjectStore, ObjectStoreFactory};
@@ -44,16 +46,39 @@ pub struct SchedulerWitnessGeneratorJob {
 pub struct SchedulerWitnessGenerator {
     object_store: Box<dyn ObjectStore>,
     prover_connection_pool: ConnectionPool,
+    completion_webhook: Option<CompletionWebhook>,
+    shutdown_timeout: Duration,
 }
 
 impl SchedulerWitnessGenerator {
     pub async fn new(
         store_factory: &ObjectStoreFactory,
         prover_connection_pool: ConnectionPool,
+        completion_webhook: Option<CompletionWebhook>,
+        shutdown_timeout: Duration,
     ) -> Self {
         Self {
-            object_store: store_factory.create_store().await,
+            object_store: store_factory
+                .create_store_for_round(AggregationRound::Scheduler)
+                .await,
             prover_connection_pool,
+            completion_webhook,
+            shutdown_timeout,
+        }
+    }
+
+    fn notify_completion(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        status: BatchCompletionStatus,
+        started_at: Instant,
+    ) {
+        if let Some(webhook) = &self.completion_webhook {
+            webhook.notify(BatchCompletionPayload::new(
+                l1_batch_number,
+                status,
+                started_at.elapsed(),
+            ));
         }
     }
 
@@ -108,16 +133,38 @@ impl JobProcessor for SchedulerWitnessGenerator {
     async fn get_next_job(&self) -> Option<(Self::JobId, Self::Job)> {
         let mut prover_connection = self.prover_connection_pool.access_storage().await;
 
-        let l1_batch_number = prover_connection
+        let metadata = prover_connection
             .fri_witness_generator_dal()
             .get_next_scheduler_witness_job()
             .await?;
+        metrics::histogram!(
+            "prover_fri.witness_generation.time_in_queue",
+            time_in_queue(metadata.created_at),
+            "aggregation_round" => format!("{:?}", AggregationRound::Scheduler),
+        );
+        let l1_batch_number = metadata.block_number;
+        // Scheduler jobs are queued once all node aggregations of the batch are complete.
+        if let Some(depth) = prover_connection
+            .fri_witness_generator_dal()
+            .get_max_node_aggregation_depth(l1_batch_number)
+            .await
+        {
+            metrics::histogram!("prover_fri.aggregation_depth", depth as f64);
+        }
         let proof_job_ids = prover_connection
             .fri_scheduler_dependency_tracker_dal()
             .get_final_prover_job_ids_for(l1_batch_number)
             .await;
         let started_at = Instant::now();
-        let proofs = load_proofs_for_job_ids(&proof_job_ids, &*self.object_store).await;
+        let proofs = load_proofs_for_job_ids(
+            &proof_job_ids,
+            &*self.object_store,
+            AggregationRound::Scheduler,
+            1,
+            false,
+        )
+        .await
+        .unwrap_or_else(|err| panic!("failed loading proofs: {err}"));
         metrics::histogram!(
                     "prover_fri.witness_generation.blob_fetch_time",
                     started_at.elapsed(),
@@ -141,13 +188,14 @@ impl JobProcessor for SchedulerWitnessGenerator {
         ))
     }
 
-    async fn save_failure(&self, job_id: L1BatchNumber, _started_at: Instant, error: String) -> () {
+    async fn save_failure(&self, job_id: L1BatchNumber, started_at: Instant, error: String) -> () {
         self.prover_connection_pool
             .access_storage()
             .await
             .fri_witness_generator_dal()
             .mark_scheduler_job_failed(&error, job_id)
             .await;
+        self.notify_completion(job_id, BatchCompletionStatus::Failed, started_at);
     }
 
     #[allow(clippy::async_yields_async)]
@@ -205,6 +253,11 @@ impl JobProcessor for SchedulerWitnessGenerator {
             .await;
 
         transaction.commit().await;
+        self.notify_completion(job_id, BatchCompletionStatus::Successful, started_at);
+    }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        Some(self.shutdown_timeout)
     }
 }
 
@@ -17,14 +17,26 @@ use zkevm_test_harness::boojum::field::goldilocks::GoldilocksField;
 use zkevm_test_harness::witness::full_block_artifact::BlockBasicCircuits;
 
 use zkevm_test_harness::zkevm_circuits::scheduler::block_header::BlockAuxilaryOutputWitness;
+use zksync_config::configs::object_store::ContentHashAlgorithm;
 use zksync_config::constants::USED_BOOTLOADER_MEMORY_BYTES;
 use zksync_object_store::{
     serialize_using_bincode, AggregationsKey, Bucket, ClosedFormInputKey, FriCircuitKey,
-    ObjectStore, StoredObject,
+    ObjectStore, ObjectStoreError, StoredObject,
 };
-use zksync_types::proofs::AggregationRound;
+use zksync_types::proofs::{AggregationRound, BaseLayerCircuitId, RecursiveLayerCircuitId};
 use zksync_types::{L1BatchNumber, U256};
 
+use crate::metrics_sink::{MetricsCrateSink, MetricsSink};
+
+use bincode::Options;
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{error, fmt};
+
 pub fn expand_bootloader_contents(packed: &[(usize, U256)]) -> Vec<u8> {
     let mut result: Vec<u8> = Vec::new();
     result.resize(USED_BOOTLOADER_MEMORY_BYTES, 0);
@@ -72,6 +84,19 @@ pub struct ClosedFormInputWrapper(
     pub(crate) RecursionQueueSimulator<GoldilocksField>,
 );
 
+impl ClosedFormInputWrapper {
+    /// Deserializes closed form inputs. In `strict` mode, blobs with trailing data are rejected;
+    /// otherwise, fields that a newer format appended to the blob are ignored.
+    pub fn deserialize_with_mode(bytes: &[u8], strict: bool) -> Result<Self, bincode::Error> {
+        let options = bincode::DefaultOptions::new().with_fixint_encoding();
+        if strict {
+            options.deserialize(bytes)
+        } else {
+            options.allow_trailing_bytes().deserialize(bytes)
+        }
+    }
+}
+
 impl StoredObject for ClosedFormInputWrapper {
     const BUCKET: Bucket = Bucket::LeafAggregationWitnessJobsFri;
     type Key<'a> = ClosedFormInputKey;
@@ -189,6 +214,9 @@ pub async fn save_base_prover_input_artifacts(
     ids_and_urls
 }
 
+/// Saves the circuits of the aggregations as prover inputs, serializing and uploading up to
+/// `concurrency` of them at a time. Returns the circuit ids and URLs in the order of `aggregations`.
+#[allow(clippy::too_many_arguments)]
 pub async fn save_recursive_layer_prover_input_artifacts(
     block_number: L1BatchNumber,
     aggregations: Vec<(
@@ -200,26 +228,76 @@ pub async fn save_recursive_layer_prover_input_artifacts(
     depth: u16,
     object_store: &dyn ObjectStore,
     base_layer_circuit_id: Option<u8>,
+    concurrency: usize,
 ) -> Vec<(u8, String)> {
-    let mut ids_and_urls = Vec::with_capacity(aggregations.len());
-    for (sequence_number, (_, _, circuit)) in aggregations.into_iter().enumerate() {
-        let circuit_id = base_layer_circuit_id.unwrap_or_else(|| circuit.numeric_circuit_type());
-        let circuit_key = FriCircuitKey {
-            block_number,
-            sequence_number,
-            circuit_id,
-            aggregation_round,
-            depth,
-        };
-        let blob_url = object_store
-            .put(circuit_key, &CircuitWrapper::Recursive(circuit))
-            .await
-            .unwrap();
-        ids_and_urls.push((circuit_id, blob_url));
-    }
-    ids_and_urls
+    let (circuit_ids, keys_and_circuits): (Vec<_>, Vec<_>) = aggregations
+        .into_iter()
+        .enumerate()
+        .map(|(sequence_number, (_, _, circuit))| {
+            let circuit_id =
+                base_layer_circuit_id.unwrap_or_else(|| circuit.numeric_circuit_type());
+            let circuit_key = FriCircuitKey {
+                block_number,
+                sequence_number,
+                circuit_id,
+                aggregation_round,
+                depth,
+            };
+            let key = CircuitWrapper::encode_key(circuit_key);
+            (circuit_id, (key, CircuitWrapper::Recursive(circuit)))
+        })
+        .unzip();
+    let urls = put_all_concurrently(
+        object_store,
+        CircuitWrapper::BUCKET,
+        aggregation_round,
+        keys_and_circuits,
+        concurrency,
+        |circuit: CircuitWrapper| {
+            circuit
+                .serialize()
+                .expect("failed serializing recursive layer circuit")
+        },
+    )
+    .await;
+    circuit_ids.into_iter().zip(urls).collect()
+}
+
+/// Serializes `values` on blocking threads and uploads them to `bucket`, processing up to
+/// `concurrency` values at a time. Returns the keys in the order of `values`.
+async fn put_all_concurrently<V, F>(
+    object_store: &dyn ObjectStore,
+    bucket: Bucket,
+    aggregation_round: AggregationRound,
+    keys_and_values: Vec<(String, V)>,
+    concurrency: usize,
+    serialize: F,
+) -> Vec<String>
+where
+    V: Send + 'static,
+    F: Fn(V) -> Vec<u8> + Send + Sync + 'static,
+{
+    let serialize = Arc::new(serialize);
+    stream::iter(keys_and_values)
+        .map(|(key, value)| {
+            let serialize = Arc::clone(&serialize);
+            async move {
+                let bytes = tokio::task::spawn_blocking(move || serialize(value))
+                    .await
+                    .expect("serialization task panicked");
+                report_blob_size(bucket, aggregation_round, bytes.len());
+                object_store.put_raw(bucket, &key, bytes).await.unwrap();
+                key
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
 }
 
+/// Saves node aggregations and returns their key, which must be persisted and used to load them.
+/// If `content_hash_algorithm` is set, the key includes the content hash of the aggregations,
+/// so that the saved blob is never overwritten by a retried job.
 pub async fn save_node_aggregations_artifacts(
     block_number: L1BatchNumber,
     circuit_id: u8,
@@ -229,37 +307,451 @@ pub async fn save_node_aggregations_artifacts(
         RecursionQueueSimulator<GoldilocksField>,
         ZkSyncRecursiveLayerCircuit,
     )>,
+    aggregation_round: AggregationRound,
     object_store: &dyn ObjectStore,
+    content_hash_algorithm: Option<ContentHashAlgorithm>,
 ) -> String {
     let key = AggregationsKey {
         block_number,
         circuit_id,
         depth,
     };
-    object_store
-        .put(key, &AggregationWrapper(aggregations))
-        .await
-        .unwrap()
+    let wrapper = AggregationWrapper(aggregations);
+    let size = bincode::serialized_size(&wrapper).expect("failed serializing node aggregations");
+    report_blob_size(AggregationWrapper::BUCKET, aggregation_round, size as usize);
+    match content_hash_algorithm {
+        Some(algorithm) => object_store
+            .put_with_content_hash(key, &wrapper, algorithm)
+            .await
+            .unwrap(),
+        None => object_store.put(key, &wrapper).await.unwrap(),
+    }
+}
+
+/// Reports the serialized size of a blob written to or read from `bucket`.
+fn report_blob_size(bucket: Bucket, aggregation_round: AggregationRound, size: usize) {
+    record_blob_size(&MetricsCrateSink, bucket, aggregation_round, size);
+}
+
+fn record_blob_size(
+    metrics_sink: &dyn MetricsSink,
+    bucket: Bucket,
+    aggregation_round: AggregationRound,
+    size: usize,
+) {
+    metrics_sink.record_histogram(
+        "prover_fri.object_store.blob_size_bytes",
+        size as f64,
+        &[
+            ("aggregation_round", format!("{:?}", aggregation_round)),
+            ("bucket", bucket.to_string()),
+        ],
+    );
 }
 
-pub fn get_recursive_layer_circuit_id_for_base_layer(base_layer_circuit_id: u8) -> u8 {
+/// Returns the id of the leaf circuit aggregating proofs of the given base layer circuit.
+pub fn get_recursive_layer_circuit_id_for_base_layer(
+    base_layer_circuit_id: BaseLayerCircuitId,
+) -> RecursiveLayerCircuitId {
     let recursive_circuit_type = base_circuit_type_into_recursive_leaf_circuit_type(
-        BaseLayerCircuitType::from_numeric_value(base_layer_circuit_id),
+        BaseLayerCircuitType::from_numeric_value(base_layer_circuit_id.0),
     );
-    recursive_circuit_type as u8
+    RecursiveLayerCircuitId(recursive_circuit_type as u8)
 }
 
 pub fn get_base_layer_circuit_id_for_recursive_layer(recursive_layer_circuit_id: u8) -> u8 {
     recursive_layer_circuit_id - ZkSyncRecursionLayerStorageType::NodeLayerCircuit as u8
 }
 
+/// Returns how long a job created at `created_at` has been waiting to be picked.
+/// Clock skew between the DB and the generator may put `created_at` in the future, which counts as no wait.
+pub fn time_in_queue(created_at: DateTime<Utc>) -> Duration {
+    (Utc::now() - created_at).to_std().unwrap_or_default()
+}
+
+/// Errors during [`load_proofs_for_job_ids()`].
+#[derive(Debug)]
+pub enum LoadProofsError {
+    /// The proof of the prover job couldn't be fetched from the object store.
+    Fetch { job_id: u32, err: ObjectStoreError },
+    /// The prover job id is listed more than once, which would aggregate its proof twice.
+    DuplicateProofJobId { id: u32 },
+}
+
+impl fmt::Display for LoadProofsError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch { job_id, err } => {
+                write!(formatter, "proof for prover job {job_id} ({err})")
+            }
+            Self::DuplicateProofJobId { id } => {
+                write!(formatter, "prover job {id} is listed more than once")
+            }
+        }
+    }
+}
+
+impl error::Error for LoadProofsError {}
+
+/// Loads proofs of the given prover jobs, fetching up to `concurrency` of them at a time.
+/// Proofs are returned in the order of `job_ids`. Duplicate job ids are rejected in `strict` mode;
+/// otherwise, only the first occurrence of each id is kept, so that every proof is loaded once.
 pub async fn load_proofs_for_job_ids(
     job_ids: &[u32],
     object_store: &dyn ObjectStore,
-) -> Vec<FriProofWrapper> {
-    let mut proofs = Vec::with_capacity(job_ids.len());
-    for &job_id in job_ids {
-        proofs.push(object_store.get(job_id).await.unwrap());
+    aggregation_round: AggregationRound,
+    concurrency: usize,
+    strict: bool,
+) -> Result<Vec<FriProofWrapper>, LoadProofsError> {
+    let job_ids = dedup_job_ids(job_ids, aggregation_round, strict)?;
+    fetch_in_order(&job_ids, concurrency, |job_id| async move {
+        let (proof, size) = get_with_size::<FriProofWrapper>(object_store, job_id)
+            .await
+            .map_err(|err| LoadProofsError::Fetch { job_id, err })?;
+        report_blob_size(FriProofWrapper::BUCKET, aggregation_round, size);
+        let circuit_id = match &proof {
+            FriProofWrapper::Base(proof) => proof.numeric_circuit_type(),
+            FriProofWrapper::Recursive(proof) => proof.numeric_circuit_type(),
+        };
+        metrics::histogram!(
+            "prover_fri.proof_load.size_bytes",
+            size as f64,
+            "circuit_id" => circuit_id.to_string(),
+        );
+        Ok(proof)
+    })
+    .await
+}
+
+fn dedup_job_ids(
+    job_ids: &[u32],
+    aggregation_round: AggregationRound,
+    strict: bool,
+) -> Result<Vec<u32>, LoadProofsError> {
+    let mut seen_ids = HashSet::with_capacity(job_ids.len());
+    let mut unique_ids = Vec::with_capacity(job_ids.len());
+    let mut duplicate_ids = vec![];
+    for &id in job_ids {
+        if seen_ids.insert(id) {
+            unique_ids.push(id);
+        } else {
+            duplicate_ids.push(id);
+        }
+    }
+    if duplicate_ids.is_empty() {
+        return Ok(unique_ids);
+    }
+
+    vlog::warn!("Duplicate prover job ids of {aggregation_round:?} job: {duplicate_ids:?}");
+    metrics::counter!(
+        "prover_fri.witness_generation.duplicate_proof_ids",
+        duplicate_ids.len() as u64,
+        "aggregation_round" => format!("{:?}", aggregation_round)
+    );
+    if strict {
+        return Err(LoadProofsError::DuplicateProofJobId {
+            id: duplicate_ids[0],
+        });
+    }
+    Ok(unique_ids)
+}
+
+/// Fetches the value for the given key together with the size of its serialized blob.
+async fn get_with_size<V: StoredObject>(
+    object_store: &dyn ObjectStore,
+    key: V::Key<'_>,
+) -> Result<(V, usize), ObjectStoreError> {
+    let bytes = object_store.get_raw(V::BUCKET, &V::encode_key(key)).await?;
+    let size = bytes.len();
+    let value = V::deserialize(bytes).map_err(ObjectStoreError::Serialization)?;
+    Ok((value, size))
+}
+
+async fn fetch_in_order<K, T, E, F, Fut>(
+    keys: &[K],
+    concurrency: usize,
+    fetch: F,
+) -> Result<Vec<T>, E>
+where
+    K: Copy,
+    F: Fn(K) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    // Unlike `buffer_unordered()`, `buffered()` yields results in the order of the keys.
+    stream::iter(keys.iter().map(|&key| fetch(key)))
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics_sink::RecordingSink;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempdir::TempDir;
+    use zksync_config::configs::object_store::ObjectStoreMode;
+    use zksync_config::ObjectStoreConfig;
+    use zksync_object_store::ObjectStoreFactory;
+
+    #[test]
+    fn base_circuit_ids_are_mapped_to_leaf_circuit_ids() {
+        let expected = [
+            (1, 3),
+            (2, 4),
+            (3, 5),
+            (4, 6),
+            (5, 7),
+            (6, 8),
+            (7, 9),
+            (8, 10),
+            (9, 11),
+            (10, 12),
+            (11, 13),
+            (12, 14),
+            (13, 15),
+        ];
+        for (base_id, leaf_id) in expected {
+            let base_id = BaseLayerCircuitId(base_id);
+            let leaf_id = RecursiveLayerCircuitId(leaf_id);
+            assert_eq!(
+                get_recursive_layer_circuit_id_for_base_layer(base_id),
+                leaf_id
+            );
+        }
+        assert_eq!(
+            (BaseLayerCircuitType::L1MessagesHasher as u8) as usize,
+            expected.len()
+        );
+    }
+
+    #[test]
+    fn time_in_queue_is_measured_from_creation() {
+        let created_at = Utc::now() - chrono::Duration::seconds(30);
+        assert!(time_in_queue(created_at) >= Duration::from_secs(30));
+
+        let created_in_future = Utc::now() + chrono::Duration::seconds(30);
+        assert_eq!(time_in_queue(created_in_future), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn duplicate_proof_job_ids_are_rejected_in_strict_mode() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let err = load_proofs_for_job_ids(
+            &[3, 5, 3],
+            &*object_store,
+            AggregationRound::LeafAggregation,
+            1,
+            true,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            LoadProofsError::DuplicateProofJobId { id: 3 }
+        ));
+
+        let job_ids = dedup_job_ids(&[3, 5, 3, 1, 5], AggregationRound::LeafAggregation, false);
+        assert_eq!(job_ids.unwrap(), [3, 5, 1]);
+    }
+
+    #[tokio::test]
+    async fn fetched_values_preserve_key_order() {
+        let completed = std::sync::Mutex::new(vec![]);
+        let values = fetch_in_order(&[1_u64, 2, 3], 3, |key| {
+            let completed = &completed;
+            async move {
+                // Later keys are fetched faster, so they complete first.
+                tokio::time::sleep(std::time::Duration::from_millis(30 - key * 10)).await;
+                completed.lock().unwrap().push(key);
+                Ok::<_, ()>(key * 10)
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(values, [10, 20, 30]);
+        assert_eq!(completed.into_inner().unwrap(), [3, 2, 1]);
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct FixtureProof(Vec<u8>);
+
+    impl StoredObject for FixtureProof {
+        const BUCKET: Bucket = Bucket::ProofsFri;
+        type Key<'a> = u32;
+
+        fn encode_key(key: Self::Key<'_>) -> String {
+            format!("proof_{key}.bin")
+        }
+
+        serialize_using_bincode!();
+    }
+
+    #[test]
+    fn blob_sizes_are_reported_for_all_rounds() {
+        let sink = RecordingSink::default();
+        for round in AggregationRound::ALL {
+            record_blob_size(&sink, FriProofWrapper::BUCKET, round, 100);
+            record_blob_size(&sink, AggregationWrapper::BUCKET, round, 0);
+        }
+
+        let expected: Vec<_> = AggregationRound::ALL
+            .iter()
+            .flat_map(|round| {
+                [
+                    (FriProofWrapper::BUCKET, round, 100.0),
+                    (AggregationWrapper::BUCKET, round, 0.0),
+                ]
+            })
+            .map(|(bucket, round, size)| {
+                let labels = vec![
+                    ("aggregation_round", format!("{round:?}")),
+                    ("bucket", bucket.to_string()),
+                ];
+                ("prover_fri.object_store.blob_size_bytes", size, labels)
+            })
+            .collect();
+        assert_eq!(sink.metrics.into_inner().unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn values_are_fetched_with_blob_size() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let proof = FixtureProof(vec![1; 100]);
+        object_store.put(7, &proof).await.unwrap();
+
+        let (fetched, size) = get_with_size::<FixtureProof>(&*object_store, 7)
+            .await
+            .unwrap();
+        assert_eq!(fetched, proof);
+        assert_eq!(size, bincode::serialize(&proof).unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn concurrent_puts_are_capped_and_preserve_order() {
+        let object_store = ObjectStoreFactory::mock().create_store().await;
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let keys_and_values: Vec<_> = (0_u8..5).map(|i| (format!("{i}.bin"), i)).collect();
+
+        let serialize = {
+            let in_flight = Arc::clone(&in_flight);
+            let max_in_flight = Arc::clone(&max_in_flight);
+            move |value: u8| {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                vec![value]
+            }
+        };
+        let keys = put_all_concurrently(
+            &*object_store,
+            Bucket::ProverJobsFri,
+            AggregationRound::LeafAggregation,
+            keys_and_values.clone(),
+            2,
+            serialize,
+        )
+        .await;
+
+        let expected_keys: Vec<_> = keys_and_values.iter().map(|(key, _)| key.clone()).collect();
+        assert_eq!(keys, expected_keys);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+        for (key, value) in keys_and_values {
+            let bytes = object_store
+                .get_raw(Bucket::ProverJobsFri, &key)
+                .await
+                .unwrap();
+            assert_eq!(bytes, [value]);
+        }
+    }
+
+    #[test]
+    fn closed_form_inputs_with_trailing_data_are_decoded_only_in_lenient_mode() {
+        let wrapper = ClosedFormInputWrapper(vec![], RecursionQueueSimulator::empty());
+        let mut bytes = bincode::serialize(&wrapper).unwrap();
+        // Simulates a field appended by a newer format version.
+        bytes.extend_from_slice(&42_u64.to_le_bytes());
+
+        let decoded = ClosedFormInputWrapper::deserialize_with_mode(&bytes, false).unwrap();
+        assert!(decoded.0.is_empty());
+        assert_eq!(decoded.1.num_items, 0);
+        assert!(ClosedFormInputWrapper::deserialize_with_mode(&bytes, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn artifacts_are_saved_and_loaded_through_file_backed_store() {
+        let dir = TempDir::new("witness-artifacts").unwrap();
+        let config = ObjectStoreConfig {
+            bucket_base_url: String::new(),
+            mode: ObjectStoreMode::FileBacked,
+            file_backed_base_path: dir.path().to_str().unwrap().to_owned(),
+            gcs_credential_file_path: String::new(),
+            max_retries: 1,
+            retry_base_backoff_ms: None,
+            content_hash_algorithm: None,
+            max_cache_bytes: None,
+            verify_artifact_integrity: None,
+            artifact_compression: None,
+            artifact_compression_level: None,
+        };
+        let object_store = ObjectStoreFactory::new(config).create_store().await;
+
+        let key = ClosedFormInputKey {
+            block_number: L1BatchNumber(1),
+            circuit_id: 3,
+        };
+        let wrapper = ClosedFormInputWrapper(vec![], RecursionQueueSimulator::empty());
+        let closed_form_inputs_key = object_store.put(key, &wrapper).await.unwrap();
+        assert_eq!(closed_form_inputs_key, "closed_form_inputs_1_3.bin");
+        let path = dir
+            .path()
+            .join(ClosedFormInputWrapper::BUCKET.to_string())
+            .join(&closed_form_inputs_key);
+        assert!(path.is_file());
+        let loaded: ClosedFormInputWrapper = object_store.get(key).await.unwrap();
+        assert!(loaded.0.is_empty());
+
+        let aggregations_key = save_node_aggregations_artifacts(
+            L1BatchNumber(1),
+            5,
+            0,
+            vec![],
+            AggregationRound::LeafAggregation,
+            &*object_store,
+            Some(ContentHashAlgorithm::Blake2b),
+        )
+        .await;
+        assert!(
+            aggregations_key.starts_with("aggregations_1_5_0_b2-"),
+            "{aggregations_key}"
+        );
+        let loaded: AggregationWrapper = object_store
+            .get_by_encoded_key(&aggregations_key)
+            .await
+            .unwrap();
+        assert!(loaded.0.is_empty());
+
+        // Without a content hash, aggregations are saved under their logical key.
+        let aggregations_key = save_node_aggregations_artifacts(
+            L1BatchNumber(1),
+            5,
+            0,
+            vec![],
+            AggregationRound::LeafAggregation,
+            &*object_store,
+            None,
+        )
+        .await;
+        assert_eq!(aggregations_key, "aggregations_1_5_0.bin");
+
+        object_store
+            .remove_raw(ClosedFormInputWrapper::BUCKET, &closed_form_inputs_key)
+            .await
+            .unwrap();
+        let err = object_store.get::<ClosedFormInputWrapper>(key).await.err();
+        assert!(matches!(err, Some(ObjectStoreError::KeyNotFound(_))));
     }
-    proofs
 }
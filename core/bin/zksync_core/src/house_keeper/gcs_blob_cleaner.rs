@@ -1,6 +1,8 @@
+use std::iter;
+
 use async_trait::async_trait;
 use zksync_dal::ConnectionPool;
-use zksync_object_store::{Bucket, ObjectStore, ObjectStoreError, ObjectStoreFactory};
+use zksync_object_store::{Bucket, ObjectStore, ObjectStoreFactory};
 
 use crate::house_keeper::periodic_job::PeriodicJob;
 
@@ -29,18 +31,6 @@ pub struct GcsBlobCleaner {
 
 const BATCH_CLEANUP_SIZE: u8 = 5;
 
-fn handle_remove_result(result: Result<(), ObjectStoreError>) {
-    if let Err(error) = result {
-        match error {
-            // There can be scenario when the removal from the GCS succeeded and updating the DB after that fails,
-            // in this scenario the retry of removal from GCS would fail as the object is already removed.
-            // Hence we ignore the KeyNotFound error below
-            ObjectStoreError::KeyNotFound(_) => {}
-            other => panic!("{:?}", other),
-        }
-    }
-}
-
 impl GcsBlobCleaner {
     pub async fn new(
         store_factory: &ObjectStoreFactory,
@@ -81,12 +71,17 @@ impl GcsBlobCleaner {
             vlog::info!("Found {} {bucket} for cleaning blobs", blob_urls.len());
         }
 
-        for (_, url) in &blob_urls {
-            let (first_url, second_url) = url.as_blob_urls();
-            handle_remove_result(self.object_store.remove_raw(bucket, first_url).await);
-            if let Some(second_url) = second_url {
-                handle_remove_result(self.object_store.remove_raw(bucket, second_url).await);
-            }
+        let keys: Vec<_> = blob_urls
+            .iter()
+            .flat_map(|(_, url)| {
+                let (first_url, second_url) = url.as_blob_urls();
+                iter::once(first_url).chain(second_url)
+            })
+            .collect();
+        // There can be scenario when the removal from the GCS succeeded and updating the DB after that fails,
+        // in this scenario the retried removal skips the already removed objects.
+        if let Err(err) = self.object_store.remove_many_raw(bucket, &keys).await {
+            panic!("{:?}", err);
         }
         blob_urls.into_iter().map(|(id, _)| id).collect()
     }
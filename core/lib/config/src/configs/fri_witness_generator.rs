@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 use std::time::Duration;
 
 // Built-in uses
@@ -24,6 +26,127 @@ pub struct FriWitnessGeneratorConfig {
     pub last_l1_batch_to_process: Option<u32>,
     // Force process block with specified number when sampling is enabled.
     pub force_process_block: Option<u32>,
+    /// Preset defining the circuits the worker picks jobs for.
+    pub role: Option<WorkerRole>,
+    // Overrides the circuit allowlist of the `role` preset.
+    pub circuit_ids_allowlist: Option<Vec<u8>>,
+    // Once more leaf aggregation jobs than this are queued, only `high_priority_circuit_ids` are picked.
+    pub queue_depth_shed_threshold: Option<usize>,
+    pub high_priority_circuit_ids: Option<Vec<u8>>,
+    // If set, a CSV row with the resources used by every completed job is appended to this file.
+    pub resource_usage_csv_path: Option<String>,
+    // Size after which the resource usage CSV is rotated.
+    pub resource_usage_csv_max_bytes: Option<u64>,
+    // Max number of distinct label combinations per metric before high-cardinality labels
+    // (e.g., `circuit_id`) are collapsed into "other".
+    pub max_metric_cardinality: Option<usize>,
+    // Once more than this many bytes were fetched from the object store within an hour,
+    // the worker stops picking new jobs until the next hour.
+    pub max_egress_bytes_per_hour: Option<u64>,
+    // Whether to reject closed form input blobs with trailing data (e.g., fields added by a newer format).
+    pub strict_closed_form_input_decoding: Option<bool>,
+    // Expected serialized size of leaf aggregation artifacts, as `<circuit_id>:<min>-<max>` entries.
+    pub artifact_size_ranges: Option<Vec<String>>,
+    // Whether jobs producing artifacts outside of the expected size range should fail.
+    pub fail_on_artifact_size_anomaly: Option<bool>,
+    // Inclusive range of l1 batches to pick jobs for, as `<from>-<to>`; used to split backfills across fleets.
+    pub batch_range: Option<String>,
+    // If set, the worker exits after processing this many jobs, waiting for new jobs until then.
+    pub max_jobs_before_exit: Option<usize>,
+    // Labels of the jobs to pick; if unset, only unlabeled jobs are picked.
+    pub claim_labels: Option<Vec<String>>,
+    // Retried jobs are picked only once this many seconds multiplied by their attempts have passed.
+    pub retry_backoff_per_job_in_secs: Option<u64>,
+    // Whether to claim the next job while the outcome of the previous one is being saved.
+    pub overlap_claim_with_save: Option<bool>,
+    // Max number of proofs fetched from the object store concurrently while preparing a job.
+    pub proof_fetch_concurrency: Option<usize>,
+    // Max number of produced circuits serialized and uploaded concurrently while saving a job.
+    pub artifact_save_concurrency: Option<usize>,
+    // If set, the scheduler witness generator posts a JSON payload to this URL once a batch is complete.
+    pub completion_webhook_url: Option<String>,
+    // Value of the `Authorization` header sent with webhook requests.
+    pub completion_webhook_auth_header: Option<String>,
+    // Whether to also post to the webhook when the scheduler witness generation of a batch fails.
+    pub completion_webhook_on_failure: Option<bool>,
+    // Whether to verify base proofs against the circuit VK before aggregating them in leaf aggregation jobs.
+    pub verify_proofs_before_aggregation: Option<bool>,
+    // Whether to read back and verify the saved artifacts of leaf aggregation jobs before marking them successful.
+    pub verify_artifacts_before_commit: Option<bool>,
+    // Whether node aggregation jobs whose leaf aggregation artifacts are missing requeue the leaf aggregation job.
+    pub reconcile_upstream_artifacts: Option<bool>,
+    // Number of threads creating the node witnesses of a single node aggregation job in parallel.
+    pub aggregation_threads: Option<usize>,
+    // Whether to claim and prepare the next job while the current one is processed; doubles peak memory
+    // for job artifacts.
+    pub prefetch_next_job: Option<bool>,
+    // If set, `/health` and `/metrics` endpoints are served on this port.
+    pub health_check_port: Option<u16>,
+    // Order in which queued leaf aggregation jobs are picked; defaults to `oldest_block_first`.
+    pub job_ordering: Option<JobOrdering>,
+    // If set, presigned URLs valid for this long are generated for the saved prover inputs.
+    pub presigned_url_ttl_in_secs: Option<u64>,
+    // Whether to fail leaf aggregation jobs listing a prover job more than once, rather than
+    // aggregating its proof once.
+    pub strict_proof_job_ids: Option<bool>,
+    // Whether to fail leaf aggregation jobs running longer than `generation_timeout_in_secs`.
+    // The aggregation itself isn't cancelled: it keeps running until completion, its result is discarded.
+    pub enforce_job_timeout: Option<bool>,
+    // Whether to append a content hash to the keys of saved node aggregations, so that a retried job
+    // never overwrites the blob read by a concurrent node aggregation job.
+    pub content_addressed_aggregations: Option<bool>,
+}
+
+/// Order in which queued jobs are picked.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOrdering {
+    /// Jobs of the oldest l1 batch are picked first, so that it's completed end-to-end
+    /// before effort is spread over newer batches.
+    #[default]
+    OldestBlockFirst,
+    /// Jobs are picked in the order they were created.
+    Fifo,
+}
+
+/// Named worker presets so that fleets can be configured with a single `role` value.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkerRole {
+    /// Picks jobs of any circuit.
+    Heavy,
+    /// Doesn't pick new jobs; used to drain a worker before it's scaled down.
+    Drain,
+}
+
+/// Effective worker settings obtained by expanding a [`WorkerRole`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkerRoleSettings {
+    /// Base layer circuit ids to pick leaf and node aggregation jobs for; `None` means all circuits.
+    /// If empty, no jobs are picked at all, including basic circuit jobs.
+    pub circuit_ids_allowlist: Option<Vec<u8>>,
+}
+
+impl WorkerRoleSettings {
+    /// Returns whether the worker picks any jobs; drained workers don't.
+    pub fn picks_jobs(&self) -> bool {
+        self.circuit_ids_allowlist
+            .as_ref()
+            .map_or(true, |circuit_ids| !circuit_ids.is_empty())
+    }
+}
+
+impl WorkerRole {
+    pub fn settings(self) -> WorkerRoleSettings {
+        match self {
+            Self::Heavy => WorkerRoleSettings {
+                circuit_ids_allowlist: None,
+            },
+            Self::Drain => WorkerRoleSettings {
+                circuit_ids_allowlist: Some(vec![]),
+            },
+        }
+    }
 }
 
 impl FriWitnessGeneratorConfig {
@@ -38,6 +161,76 @@ impl FriWitnessGeneratorConfig {
     pub fn last_l1_batch_to_process(&self) -> u32 {
         self.last_l1_batch_to_process.unwrap_or(u32::MAX)
     }
+
+    pub fn retry_backoff_per_job(&self) -> Option<Duration> {
+        self.retry_backoff_per_job_in_secs.map(Duration::from_secs)
+    }
+
+    pub fn presigned_url_ttl(&self) -> Option<Duration> {
+        self.presigned_url_ttl_in_secs.map(Duration::from_secs)
+    }
+
+    /// Returns the max time a leaf aggregation job may be processed for, if it's enforced.
+    pub fn job_timeout(&self) -> Option<Duration> {
+        self.enforce_job_timeout
+            .unwrap_or(false)
+            .then(|| self.witness_generation_timeout())
+    }
+
+    /// Expands the configured `role` (if any) applying the explicitly set overrides on top of it.
+    pub fn worker_settings(&self) -> WorkerRoleSettings {
+        let mut settings = self.role.map(WorkerRole::settings).unwrap_or_default();
+        if let Some(allowlist) = &self.circuit_ids_allowlist {
+            settings.circuit_ids_allowlist = Some(allowlist.clone());
+        }
+        settings
+    }
+
+    /// Returns the number of threads used to create witnesses of a job. The configured
+    /// `aggregation_threads` are capped by `available_parallelism`.
+    pub fn aggregation_threads(&self, available_parallelism: usize) -> usize {
+        self.aggregation_threads
+            .unwrap_or(1)
+            .clamp(1, available_parallelism.max(1))
+    }
+
+    /// Returns the expected serialized size of leaf aggregation artifacts for the circuit, if configured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an `artifact_size_ranges` entry is malformed.
+    pub fn artifact_size_range(&self, circuit_id: u8) -> Option<RangeInclusive<u64>> {
+        self.artifact_size_ranges
+            .as_ref()?
+            .iter()
+            .find_map(|entry| {
+                let (id, range) = parse_artifact_size_range(entry)
+                    .unwrap_or_else(|| panic!("malformed artifact size range `{}`", entry));
+                (id == circuit_id).then_some(range)
+            })
+    }
+
+    /// Returns the inclusive range of l1 batches to pick jobs for, if configured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_range` is malformed.
+    pub fn batch_range(&self) -> Option<RangeInclusive<u32>> {
+        let batch_range = self.batch_range.as_ref()?;
+        let range = parse_range(batch_range)
+            .unwrap_or_else(|| panic!("malformed batch range `{}`", batch_range));
+        Some(range)
+    }
+}
+
+fn parse_artifact_size_range(entry: &str) -> Option<(u8, RangeInclusive<u64>)> {
+    let (circuit_id, range) = entry.split_once(':')?;
+    Some((circuit_id.trim().parse().ok()?, parse_range(range)?))
+}
+
+fn parse_range<T: FromStr>(range: &str) -> Option<RangeInclusive<T>> {
+    let (start, end) = range.split_once('-')?;
+    Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
 }
 
 #[cfg(test)]
@@ -54,6 +247,38 @@ mod tests {
             dump_arguments_for_blocks: vec![2, 3],
             last_l1_batch_to_process: None,
             force_process_block: Some(1),
+            role: None,
+            circuit_ids_allowlist: None,
+            queue_depth_shed_threshold: None,
+            high_priority_circuit_ids: None,
+            resource_usage_csv_path: None,
+            resource_usage_csv_max_bytes: None,
+            max_metric_cardinality: None,
+            max_egress_bytes_per_hour: None,
+            strict_closed_form_input_decoding: None,
+            artifact_size_ranges: None,
+            fail_on_artifact_size_anomaly: None,
+            batch_range: None,
+            max_jobs_before_exit: None,
+            claim_labels: None,
+            retry_backoff_per_job_in_secs: None,
+            overlap_claim_with_save: None,
+            proof_fetch_concurrency: None,
+            artifact_save_concurrency: None,
+            completion_webhook_url: None,
+            completion_webhook_auth_header: None,
+            completion_webhook_on_failure: None,
+            verify_proofs_before_aggregation: None,
+            verify_artifacts_before_commit: None,
+            reconcile_upstream_artifacts: None,
+            aggregation_threads: None,
+            prefetch_next_job: None,
+            health_check_port: None,
+            job_ordering: None,
+            presigned_url_ttl_in_secs: None,
+            strict_proof_job_ids: None,
+            enforce_job_timeout: None,
+            content_addressed_aggregations: None,
         }
     }
 
@@ -70,4 +295,81 @@ mod tests {
         let actual = FriWitnessGeneratorConfig::from_env();
         assert_eq!(actual, expected_config());
     }
+
+    #[test]
+    fn role_expands_with_overrides() {
+        let config = FriWitnessGeneratorConfig {
+            role: Some(WorkerRole::Drain),
+            circuit_ids_allowlist: Some(vec![1, 3]),
+            ..expected_config()
+        };
+        let expected = WorkerRoleSettings {
+            circuit_ids_allowlist: Some(vec![1, 3]),
+        };
+        assert_eq!(config.worker_settings(), expected);
+        assert_eq!(
+            expected_config().worker_settings(),
+            WorkerRoleSettings::default()
+        );
+    }
+
+    #[test]
+    fn drained_workers_pick_no_jobs() {
+        let config = FriWitnessGeneratorConfig {
+            role: Some(WorkerRole::Drain),
+            ..expected_config()
+        };
+        assert!(!config.worker_settings().picks_jobs());
+        assert!(expected_config().worker_settings().picks_jobs());
+
+        let config = FriWitnessGeneratorConfig {
+            role: Some(WorkerRole::Drain),
+            circuit_ids_allowlist: Some(vec![3]),
+            ..expected_config()
+        };
+        assert!(config.worker_settings().picks_jobs());
+    }
+
+    #[test]
+    fn artifact_size_ranges_are_parsed() {
+        let config = FriWitnessGeneratorConfig {
+            artifact_size_ranges: Some(vec!["1:100-200".to_owned(), "3:1000-5000".to_owned()]),
+            ..expected_config()
+        };
+        assert_eq!(config.artifact_size_range(3), Some(1000..=5000));
+        assert_eq!(config.artifact_size_range(2), None);
+        assert_eq!(expected_config().artifact_size_range(1), None);
+    }
+
+    #[test]
+    fn batch_range_is_parsed() {
+        let config = FriWitnessGeneratorConfig {
+            batch_range: Some("100-200".to_owned()),
+            ..expected_config()
+        };
+        assert_eq!(config.batch_range(), Some(100..=200));
+        assert_eq!(expected_config().batch_range(), None);
+    }
+
+    #[test]
+    fn job_timeout_is_opt_in() {
+        let config = FriWitnessGeneratorConfig {
+            enforce_job_timeout: Some(true),
+            ..expected_config()
+        };
+        assert_eq!(config.job_timeout(), Some(Duration::from_secs(900)));
+        assert_eq!(expected_config().job_timeout(), None);
+    }
+
+    #[test]
+    fn aggregation_threads_are_capped_by_available_parallelism() {
+        let config = FriWitnessGeneratorConfig {
+            aggregation_threads: Some(8),
+            ..expected_config()
+        };
+        assert_eq!(config.aggregation_threads(16), 8);
+        assert_eq!(config.aggregation_threads(4), 4);
+        assert_eq!(config.aggregation_threads(0), 1);
+        assert_eq!(expected_config().aggregation_threads(16), 1);
+    }
 }
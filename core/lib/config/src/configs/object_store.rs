@@ -1,5 +1,6 @@
 use super::envy_load;
 use serde::Deserialize;
+use std::time::Duration;
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy)]
 pub enum ObjectStoreMode {
@@ -8,6 +9,13 @@ pub enum ObjectStoreMode {
     FileBacked,
 }
 
+/// Hash algorithm used for content-addressed object keys.
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy)]
+pub enum ContentHashAlgorithm {
+    Sha256,
+    Blake2b,
+}
+
 /// Configuration for the object store
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct ObjectStoreConfig {
@@ -16,9 +24,26 @@ pub struct ObjectStoreConfig {
     pub file_backed_base_path: String,
     pub gcs_credential_file_path: String,
     pub max_retries: u16,
+    /// Base backoff of retrying failed store operations, doubled after each attempt. If set,
+    /// every store retries transient errors up to `max_retries` times.
+    pub retry_base_backoff_ms: Option<u64>,
+    /// Algorithm for content-addressed keys; SHA-256 if not set.
+    pub content_hash_algorithm: Option<ContentHashAlgorithm>,
+    /// Total size of the fetched blobs cached in memory. If not set, fetched blobs aren't cached.
+    pub max_cache_bytes: Option<u64>,
+    /// Whether to store a checksum alongside every blob and check fetched blobs against it.
+    /// Blobs written without a checksum are still loaded.
+    pub verify_artifact_integrity: Option<bool>,
+    /// Whether to compress written blobs with zstd. Compressed blobs are loaded regardless
+    /// of this setting.
+    pub artifact_compression: Option<bool>,
+    /// zstd compression level; 3 if not set.
+    pub artifact_compression_level: Option<i32>,
 }
 
 impl ObjectStoreConfig {
+    const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
     pub fn from_env() -> Self {
         envy_load("object_store", "OBJECT_STORE_")
     }
@@ -26,6 +51,44 @@ impl ObjectStoreConfig {
     pub fn public_from_env() -> Self {
         envy_load("public_object_store", "PUBLIC_OBJECT_STORE_")
     }
+
+    /// Loads the config of a separate object store for the given aggregation round
+    /// (e.g., `scheduler`) from `{ROUND}_OBJECT_STORE_` variables, if it's configured.
+    pub fn round_from_env(round: &str) -> Option<Self> {
+        let prefix = format!("{}_OBJECT_STORE_", round.to_uppercase());
+        std::env::var(format!("{}MODE", prefix)).ok()?;
+        Some(envy_load("round_object_store", &prefix))
+    }
+
+    /// Loads the config of the store that blobs missing from the main store are read from
+    /// (e.g., during a store migration) from `SECONDARY_OBJECT_STORE_` variables, if it's set.
+    pub fn secondary_from_env() -> Option<Self> {
+        std::env::var("SECONDARY_OBJECT_STORE_MODE").ok()?;
+        Some(envy_load(
+            "secondary_object_store",
+            "SECONDARY_OBJECT_STORE_",
+        ))
+    }
+
+    pub fn content_hash_algorithm(&self) -> ContentHashAlgorithm {
+        self.content_hash_algorithm
+            .unwrap_or(ContentHashAlgorithm::Sha256)
+    }
+
+    pub fn retry_base_backoff(&self) -> Option<Duration> {
+        self.retry_base_backoff_ms.map(Duration::from_millis)
+    }
+
+    /// Returns the zstd level to compress written blobs with, or `None` if compression is disabled.
+    pub fn artifact_compression_level(&self) -> Option<i32> {
+        if !self.artifact_compression.unwrap_or(false) {
+            return None;
+        }
+        Some(
+            self.artifact_compression_level
+                .unwrap_or(Self::DEFAULT_COMPRESSION_LEVEL),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -40,6 +103,12 @@ mod tests {
             file_backed_base_path: "artifacts".to_string(),
             gcs_credential_file_path: "/path/to/credentials.json".to_string(),
             max_retries: 5,
+            retry_base_backoff_ms: None,
+            content_hash_algorithm: None,
+            max_cache_bytes: None,
+            verify_artifact_integrity: None,
+            artifact_compression: None,
+            artifact_compression_level: None,
         }
     }
 
@@ -70,4 +139,57 @@ PUBLIC_OBJECT_STORE_MAX_RETRIES="5"
         let actual = ObjectStoreConfig::public_from_env();
         assert_eq!(actual, expected_config("/public_base_url"));
     }
+
+    #[test]
+    fn round_config_from_env() {
+        let config = r#"
+SCHEDULER_OBJECT_STORE_BUCKET_BASE_URL="/scheduler_base_url"
+SCHEDULER_OBJECT_STORE_MODE="FileBacked"
+SCHEDULER_OBJECT_STORE_FILE_BACKED_BASE_PATH="artifacts"
+SCHEDULER_OBJECT_STORE_GCS_CREDENTIAL_FILE_PATH="/path/to/credentials.json"
+SCHEDULER_OBJECT_STORE_MAX_RETRIES="5"
+        "#;
+        set_env(config);
+        let actual = ObjectStoreConfig::round_from_env("scheduler");
+        assert_eq!(actual, Some(expected_config("/scheduler_base_url")));
+        assert_eq!(ObjectStoreConfig::round_from_env("node_aggregation"), None);
+    }
+
+    #[test]
+    fn secondary_config_from_env() {
+        let config = r#"
+SECONDARY_OBJECT_STORE_BUCKET_BASE_URL="/secondary_base_url"
+SECONDARY_OBJECT_STORE_MODE="FileBacked"
+SECONDARY_OBJECT_STORE_FILE_BACKED_BASE_PATH="artifacts"
+SECONDARY_OBJECT_STORE_GCS_CREDENTIAL_FILE_PATH="/path/to/credentials.json"
+SECONDARY_OBJECT_STORE_MAX_RETRIES="5"
+        "#;
+        set_env(config);
+        let actual = ObjectStoreConfig::secondary_from_env();
+        assert_eq!(actual, Some(expected_config("/secondary_base_url")));
+    }
+
+    #[test]
+    fn content_hash_algorithm_is_sha256_by_default() {
+        let mut config = expected_config("/base/url");
+        assert_eq!(
+            config.content_hash_algorithm(),
+            ContentHashAlgorithm::Sha256
+        );
+        config.content_hash_algorithm = Some(ContentHashAlgorithm::Blake2b);
+        assert_eq!(
+            config.content_hash_algorithm(),
+            ContentHashAlgorithm::Blake2b
+        );
+    }
+
+    #[test]
+    fn artifact_compression_is_disabled_by_default() {
+        let mut config = expected_config("/base/url");
+        assert_eq!(config.artifact_compression_level(), None);
+        config.artifact_compression = Some(true);
+        assert_eq!(config.artifact_compression_level(), Some(3));
+        config.artifact_compression_level = Some(19);
+        assert_eq!(config.artifact_compression_level(), Some(19));
+    }
 }
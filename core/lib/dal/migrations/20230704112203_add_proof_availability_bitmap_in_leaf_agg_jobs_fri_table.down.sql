@@ -0,0 +1 @@
+ALTER TABLE leaf_aggregation_witness_jobs_fri DROP COLUMN IF EXISTS proof_availability_bitmap;
@@ -0,0 +1 @@
+ALTER TABLE leaf_aggregation_witness_jobs_fri ADD COLUMN IF NOT EXISTS job_label TEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS maintenance_locks
+(
+    name         TEXT      PRIMARY KEY,
+    locked_until TIMESTAMP NOT NULL,
+    created_at   TIMESTAMP NOT NULL,
+    updated_at   TIMESTAMP NOT NULL
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS node_aggregation_job_dependencies_fri;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS node_aggregation_job_dependencies_fri
+(
+    node_aggregation_job_id BIGINT NOT NULL REFERENCES node_aggregation_witness_jobs_fri (id) ON DELETE CASCADE,
+    prover_job_id           BIGINT NOT NULL REFERENCES prover_jobs_fri (id) ON DELETE CASCADE,
+    created_at              TIMESTAMP NOT NULL,
+    PRIMARY KEY (node_aggregation_job_id, prover_job_id)
+);
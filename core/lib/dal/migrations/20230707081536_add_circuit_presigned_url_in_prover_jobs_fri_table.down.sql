@@ -0,0 +1 @@
+ALTER TABLE prover_jobs_fri DROP COLUMN IF EXISTS circuit_presigned_url;
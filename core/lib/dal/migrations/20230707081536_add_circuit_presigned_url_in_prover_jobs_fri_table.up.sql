@@ -0,0 +1 @@
+ALTER TABLE prover_jobs_fri ADD COLUMN IF NOT EXISTS circuit_presigned_url TEXT;
@@ -23,20 +23,30 @@ impl FriProverDal<'_, '_> {
         depth: u16,
     ) {
         let started_at = Instant::now();
-        for (sequence_number, (circuit_id, circuit_blob_url)) in
-            circuit_ids_and_urls.iter().enumerate()
-        {
-            self.insert_prover_job(
-                l1_batch_number,
-                *circuit_id,
-                depth,
-                sequence_number,
-                aggregation_round,
-                circuit_blob_url,
-                false,
-            )
-            .await;
-        }
+        // Jobs are numbered in the order of `circuit_ids_and_urls`, same as for `insert_prover_job`.
+        let sequence_numbers: Vec<i32> = (0..circuit_ids_and_urls.len() as i32).collect();
+        let (circuit_ids, circuit_blob_urls): (Vec<i16>, Vec<String>) = circuit_ids_and_urls
+            .into_iter()
+            .map(|(circuit_id, circuit_blob_url)| (circuit_id as i16, circuit_blob_url))
+            .unzip();
+        sqlx::query!(
+                    "
+                    INSERT INTO prover_jobs_fri (l1_batch_number, circuit_id, circuit_blob_url, aggregation_round, sequence_number, depth, is_node_final_proof, status, created_at, updated_at)
+                    SELECT $1::bigint, u.circuit_id, u.circuit_blob_url, $5::smallint, u.sequence_number, $6::int, false, 'queued', now(), now()
+                    FROM UNNEST($2::smallint[], $3::text[], $4::int[]) AS u(circuit_id, circuit_blob_url, sequence_number)
+                    ON CONFLICT(l1_batch_number, aggregation_round, circuit_id, depth, sequence_number)
+                    DO UPDATE SET updated_at=now()
+                    ",
+                    l1_batch_number.0 as i64,
+                    &circuit_ids,
+                    &circuit_blob_urls,
+                    &sequence_numbers,
+                    aggregation_round as i16,
+                    depth as i32,
+                )
+            .execute(self.storage.conn())
+            .await
+            .unwrap();
         metrics::histogram!("dal.request", started_at.elapsed(), "method" => "save_fri_prover_jobs");
     }
 
@@ -143,6 +153,81 @@ impl FriProverDal<'_, '_> {
         }
     }
 
+    /// Moves the job from the `from` to the `to` status (e.g. for jobs handed over to external provers),
+    /// provided that it is still in the `from` status. Returns whether the transition happened.
+    pub async fn transition_job_state(&mut self, id: u32, from: &str, to: &str) -> bool {
+        let result = sqlx::query!(
+            "
+            UPDATE prover_jobs_fri
+            SET status = $3, updated_at = now()
+            WHERE id = $1 AND status = $2
+            ",
+            id as i64,
+            from,
+            to,
+        )
+        .execute(self.storage.conn())
+        .await
+        .unwrap();
+        result.rows_affected() == 1
+    }
+
+    pub async fn get_circuit_blob_url(&mut self, id: u32) -> Option<String> {
+        sqlx::query!(
+            "
+            SELECT circuit_blob_url FROM prover_jobs_fri
+            WHERE id = $1
+            ",
+            id as i64,
+        )
+        .fetch_optional(self.storage.conn())
+        .await
+        .unwrap()
+        .map(|row| row.circuit_blob_url)
+    }
+
+    /// Stores presigned URLs of prover job inputs, keyed by the `circuit_blob_url` of the jobs.
+    pub async fn set_circuit_presigned_urls(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+        aggregation_round: AggregationRound,
+        presigned_urls: Vec<(String, String)>,
+    ) {
+        let (circuit_blob_urls, circuit_presigned_urls): (Vec<String>, Vec<String>) =
+            presigned_urls.into_iter().unzip();
+        sqlx::query!(
+            "
+            UPDATE prover_jobs_fri
+            SET circuit_presigned_url = u.circuit_presigned_url, updated_at = now()
+            FROM UNNEST($1::text[], $2::text[]) AS u(circuit_blob_url, circuit_presigned_url)
+            WHERE l1_batch_number = $3
+              AND aggregation_round = $4
+              AND prover_jobs_fri.circuit_blob_url = u.circuit_blob_url
+            ",
+            &circuit_blob_urls,
+            &circuit_presigned_urls,
+            l1_batch_number.0 as i64,
+            aggregation_round as i16,
+        )
+        .execute(self.storage.conn())
+        .await
+        .unwrap();
+    }
+
+    pub async fn get_circuit_presigned_url(&mut self, id: u32) -> Option<String> {
+        sqlx::query!(
+            "
+            SELECT circuit_presigned_url FROM prover_jobs_fri
+            WHERE id = $1
+            ",
+            id as i64,
+        )
+        .fetch_optional(self.storage.conn())
+        .await
+        .unwrap()
+        .and_then(|row| row.circuit_presigned_url)
+    }
+
     pub async fn save_proof(
         &mut self,
         id: u32,
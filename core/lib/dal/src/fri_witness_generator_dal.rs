@@ -1,10 +1,13 @@
+use sqlx::types::chrono::{DateTime, Utc};
 use sqlx::Row;
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::time::{Duration, Instant};
 
+use zksync_config::configs::fri_witness_generator::JobOrdering;
 use zksync_types::proofs::{
-    AggregationRound, JobCountStatistics, LeafAggregationJobMetadata, NodeAggregationJobMetadata,
-    StuckJobs,
+    AggregationRound, BaseLayerCircuitId, JobCountStatistics, LeafAggregationJobMetadata,
+    LeafAggregationJobRecord, NodeAggregationJobMetadata, SchedulerWitnessJobMetadata, StuckJobs,
 };
 use zksync_types::L1BatchNumber;
 
@@ -127,26 +130,108 @@ impl FriWitnessGeneratorDal<'_, '_> {
         .unwrap();
     }
 
-    pub async fn mark_leaf_aggregation_job_failed(&mut self, error: &str, id: u32) {
+    /// Marks the leaf aggregation job as failed. Once the job reaches `max_attempts`, it's marked
+    /// as `max_attempts_exceeded` instead, which is terminal: such jobs are never requeued, so that
+    /// a job failing deterministically doesn't waste capacity forever.
+    pub async fn mark_leaf_aggregation_job_failed(
+        &mut self,
+        error: &str,
+        id: u32,
+        max_attempts: u32,
+    ) {
         sqlx::query!(
             "
                 UPDATE leaf_aggregation_witness_jobs_fri
-                SET status ='failed', error= $1, updated_at = now()
+                SET status = CASE WHEN attempts >= $3 THEN 'max_attempts_exceeded' ELSE 'failed' END,
+                    error = $1, updated_at = now()
                 WHERE id = $2
                ",
             error,
-            id as i64
+            id as i64,
+            max_attempts as i16
         )
         .execute(self.storage.conn())
         .await
         .unwrap();
     }
 
+    /// Requeues the successful leaf aggregation job of the given batch and circuit, whose artifacts
+    /// turned out to be missing from the object store, and returns the dependent node aggregation job
+    /// to waiting for proofs until the leaf aggregation job succeeds again. Returns `false` and leaves
+    /// the node aggregation job intact if the leaf aggregation job isn't successful (e.g., because
+    /// it's already being reprocessed).
+    pub async fn requeue_leaf_aggregation_job_with_missing_artifacts(
+        &mut self,
+        block_number: L1BatchNumber,
+        circuit_id: u8,
+        node_aggregation_job_id: u32,
+    ) -> bool {
+        let mut transaction = self.storage.start_transaction().await;
+        let requeued = sqlx::query!(
+            "
+                UPDATE leaf_aggregation_witness_jobs_fri
+                SET status = 'queued', error = 'artifacts missing from the object store', updated_at = now()
+                WHERE l1_batch_number = $1
+                AND circuit_id = $2
+                AND status = 'successful'
+                RETURNING id
+               ",
+            block_number.0 as i64,
+            circuit_id as i16,
+        )
+        .fetch_optional(transaction.conn())
+        .await
+        .unwrap()
+        .is_some();
+        if requeued {
+            // `number_of_dependent_jobs` is set again once the leaf aggregation job succeeds,
+            // keeping the job from being queued before that.
+            sqlx::query!(
+                "
+                    UPDATE node_aggregation_witness_jobs_fri
+                    SET status = 'waiting_for_proofs', number_of_dependent_jobs = NULL, updated_at = now()
+                    WHERE id = $1
+                   ",
+                node_aggregation_job_id as i64
+            )
+            .execute(transaction.conn())
+            .await
+            .unwrap();
+        }
+        transaction.commit().await;
+        requeued
+    }
+
+    /// Requeues failed leaf aggregation jobs (including ones that exceeded the max attempts)
+    /// of the given batch and / or with the given id, resetting their attempts and errors, so that
+    /// they're reprocessed after the failure cause is fixed. Returns the number of requeued jobs.
+    pub async fn requeue_failed_leaf_aggregation_jobs(
+        &mut self,
+        block_number: Option<L1BatchNumber>,
+        id: Option<u32>,
+    ) -> u64 {
+        sqlx::query!(
+            "
+                UPDATE leaf_aggregation_witness_jobs_fri
+                SET status = 'queued', attempts = 0, error = NULL, updated_at = now()
+                WHERE status IN ('failed', 'max_attempts_exceeded')
+                AND ($1::bigint IS NULL OR l1_batch_number = $1)
+                AND ($2::bigint IS NULL OR id = $2)
+               ",
+            block_number.map(|number| number.0 as i64),
+            id.map(|id| id as i64),
+        )
+        .execute(self.storage.conn())
+        .await
+        .unwrap()
+        .rows_affected()
+    }
+
     pub async fn mark_leaf_aggregation_as_successful(&mut self, id: u32, time_taken: Duration) {
         sqlx::query!(
             "
                 UPDATE leaf_aggregation_witness_jobs_fri
-                SET status = 'successful', updated_at = now(), time_taken = $1
+                SET status = 'successful', updated_at = now(), completed_at = now(), time_taken = $1
                 WHERE id = $2
                ",
             duration_to_naive_time(time_taken),
@@ -183,6 +268,29 @@ impl FriWitnessGeneratorDal<'_, '_> {
             .collect()
     }
 
+    /// Tries to take the lease of the maintenance task `name` for `ttl`, so that the task
+    /// is run by a single worker at a time. Returns whether the lease was taken; it expires
+    /// automatically, so a crashed holder doesn't block the task forever.
+    pub async fn try_acquire_maintenance_lock(&mut self, name: &str, ttl: Duration) -> bool {
+        let ttl = pg_interval_from_duration(ttl);
+        sqlx::query!(
+            "
+                INSERT INTO maintenance_locks (name, locked_until, created_at, updated_at)
+                VALUES ($1, now() + $2::interval, now(), now())
+                ON CONFLICT (name) DO UPDATE
+                SET locked_until = now() + $2::interval, updated_at = now()
+                WHERE maintenance_locks.locked_until <= now()
+                RETURNING name
+                ",
+            name,
+            &ttl,
+        )
+        .fetch_optional(self.storage.conn())
+        .await
+        .unwrap()
+        .is_some()
+    }
+
     pub async fn create_aggregation_jobs(
         &mut self,
         block_number: L1BatchNumber,
@@ -198,8 +306,8 @@ impl FriWitnessGeneratorDal<'_, '_> {
                 sqlx::query!(
                     "
                     INSERT INTO leaf_aggregation_witness_jobs_fri
-                        (l1_batch_number, circuit_id, closed_form_inputs_blob_url, number_of_basic_circuits, status, created_at, updated_at)
-                    VALUES ($1, $2, $3, $4, 'waiting_for_proofs', now(), now())
+                        (l1_batch_number, circuit_id, closed_form_inputs_blob_url, number_of_basic_circuits, proof_availability_bitmap, status, created_at, updated_at)
+                    VALUES ($1, $2, $3, $4, repeat('0', $4)::varbit, 'waiting_for_proofs', now(), now())
                     ON CONFLICT(l1_batch_number, circuit_id)
                     DO UPDATE SET updated_at=now()
                     ",
@@ -254,7 +362,23 @@ impl FriWitnessGeneratorDal<'_, '_> {
         }
     }
 
-    pub async fn get_next_leaf_aggregation_job(&mut self) -> Option<LeafAggregationJobMetadata> {
+    /// Picks the next queued leaf aggregation job. If `circuit_ids` or `batch_range` are set,
+    /// only jobs for these circuits and l1 batches are considered. Jobs labeled with
+    /// [`Self::label_leaf_aggregation_jobs()`] are only picked if their label is in `labels`;
+    /// without `labels`, only unlabeled jobs are picked. If `retry_backoff` is set, retried jobs
+    /// are only picked once `retry_backoff` multiplied by the number of attempts has passed
+    /// since they were requeued. Among the matching jobs, the one coming first in `ordering` is picked.
+    pub async fn get_next_leaf_aggregation_job(
+        &mut self,
+        circuit_ids: Option<&[u8]>,
+        batch_range: Option<RangeInclusive<L1BatchNumber>>,
+        labels: Option<&[String]>,
+        retry_backoff: Option<Duration>,
+        ordering: JobOrdering,
+    ) -> Option<LeafAggregationJobMetadata> {
+        let circuit_ids: Option<Vec<i16>> =
+            circuit_ids.map(|ids| ids.iter().map(|&id| id as i16).collect());
+        let retry_backoff = retry_backoff.map(pg_interval_from_duration);
         let row = sqlx::query!(
             "
                 UPDATE leaf_aggregation_witness_jobs_fri
@@ -264,13 +388,26 @@ impl FriWitnessGeneratorDal<'_, '_> {
                     SELECT id
                     FROM leaf_aggregation_witness_jobs_fri
                     WHERE status = 'queued'
-                    ORDER BY l1_batch_number ASC, id ASC
+                    AND ($1::smallint[] IS NULL OR circuit_id = ANY($1))
+                    AND ($2::bigint IS NULL OR l1_batch_number >= $2)
+                    AND ($3::bigint IS NULL OR l1_batch_number <= $3)
+                    AND (($4::text[] IS NULL AND job_label IS NULL) OR job_label = ANY($4))
+                    AND ($5::interval IS NULL OR attempts = 0 OR updated_at <= now() - $5::interval * attempts)
+                    ORDER BY CASE WHEN $6::bool THEN l1_batch_number ELSE 0 END ASC, id ASC
                     LIMIT 1
                     FOR UPDATE
                     SKIP LOCKED
                 )
-                RETURNING leaf_aggregation_witness_jobs_fri.*
+                RETURNING leaf_aggregation_witness_jobs_fri.id, leaf_aggregation_witness_jobs_fri.l1_batch_number,
+                leaf_aggregation_witness_jobs_fri.circuit_id, leaf_aggregation_witness_jobs_fri.attempts,
+                leaf_aggregation_witness_jobs_fri.created_at
                 ",
+            circuit_ids.as_deref(),
+            batch_range.as_ref().map(|range| range.start().0 as i64),
+            batch_range.as_ref().map(|range| range.end().0 as i64),
+            labels,
+            retry_backoff,
+            ordering == JobOrdering::OldestBlockFirst,
         )
         .fetch_optional(self.storage.conn())
         .await
@@ -287,11 +424,73 @@ impl FriWitnessGeneratorDal<'_, '_> {
         Some(LeafAggregationJobMetadata {
             id: row.id as u32,
             block_number,
-            circuit_id: row.circuit_id as u8,
+            circuit_id: BaseLayerCircuitId(row.circuit_id as u8),
             prover_job_ids_for_proofs: proof_job_ids,
+            attempts: row.attempts as u32,
+            created_at: DateTime::<Utc>::from_utc(row.created_at, Utc),
         })
     }
 
+    /// Returns all queued leaf aggregation jobs without picking them, i.e. their status and attempts
+    /// are left intact and workers can still pick them concurrently.
+    pub async fn get_queued_leaf_aggregation_jobs(&mut self) -> Vec<LeafAggregationJobMetadata> {
+        let rows = sqlx::query!(
+            "
+                SELECT id, l1_batch_number, circuit_id, attempts, created_at
+                FROM leaf_aggregation_witness_jobs_fri
+                WHERE status = 'queued'
+                ORDER BY l1_batch_number ASC, id ASC
+                "
+        )
+        .fetch_all(self.storage.conn())
+        .await
+        .unwrap();
+
+        let mut jobs = Vec::with_capacity(rows.len());
+        for row in rows {
+            let block_number = L1BatchNumber(row.l1_batch_number as u32);
+            let proof_job_ids = self
+                .prover_job_ids_for(
+                    block_number,
+                    row.circuit_id as u8,
+                    AggregationRound::BasicCircuits,
+                    0,
+                )
+                .await;
+            jobs.push(LeafAggregationJobMetadata {
+                id: row.id as u32,
+                block_number,
+                circuit_id: BaseLayerCircuitId(row.circuit_id as u8),
+                prover_job_ids_for_proofs: proof_job_ids,
+                attempts: row.attempts as u32,
+                created_at: DateTime::<Utc>::from_utc(row.created_at, Utc),
+            });
+        }
+        jobs
+    }
+
+    /// Labels leaf aggregation jobs of the given l1 batches, so that they're only picked
+    /// by workers claiming this label.
+    pub async fn label_leaf_aggregation_jobs(
+        &mut self,
+        batch_range: RangeInclusive<L1BatchNumber>,
+        label: &str,
+    ) {
+        sqlx::query!(
+            "
+                UPDATE leaf_aggregation_witness_jobs_fri
+                SET job_label = $1, updated_at = now()
+                WHERE l1_batch_number >= $2 AND l1_batch_number <= $3
+                ",
+            label,
+            batch_range.start().0 as i64,
+            batch_range.end().0 as i64,
+        )
+        .execute(self.storage.conn())
+        .await
+        .unwrap();
+    }
+
     async fn prover_job_ids_for(
         &mut self,
         block_number: L1BatchNumber,
@@ -322,6 +521,112 @@ impl FriWitnessGeneratorDal<'_, '_> {
         .collect::<_>()
     }
 
+    /// Exports all leaf aggregation jobs that weren't successfully processed yet.
+    ///
+    /// Only the leaf aggregation round is covered: jobs of later rounds track their inputs by ids
+    /// of prover jobs (node aggregation job dependencies, the scheduler dependency tracker), which
+    /// aren't part of the snapshot, so restored jobs of these rounds would have dangling dependencies.
+    pub async fn export_leaf_aggregation_job_queue(&mut self) -> Vec<LeafAggregationJobRecord> {
+        sqlx::query!(
+            r#"
+                SELECT l1_batch_number, circuit_id, closed_form_inputs_blob_url,
+                    number_of_basic_circuits, proof_availability_bitmap::text AS "proof_availability_bitmap?",
+                    job_label, status, attempts
+                FROM leaf_aggregation_witness_jobs_fri
+                WHERE status != 'successful'
+                ORDER BY l1_batch_number ASC, circuit_id ASC
+                "#
+        )
+        .fetch_all(self.storage.conn())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| LeafAggregationJobRecord {
+            block_number: L1BatchNumber(row.l1_batch_number as u32),
+            circuit_id: row.circuit_id as u8,
+            closed_form_inputs_blob_url: row.closed_form_inputs_blob_url,
+            number_of_basic_circuits: row.number_of_basic_circuits.map(|count| count as usize),
+            proof_availability_bitmap: row.proof_availability_bitmap,
+            job_label: row.job_label,
+            status: row.status,
+            attempts: row.attempts as u32,
+        })
+        .collect()
+    }
+
+    /// Restores leaf aggregation jobs exported with [`Self::export_leaf_aggregation_job_queue()`].
+    /// Jobs that were in progress are requeued, since the workers processing them are gone.
+    /// Jobs that already exist are left intact.
+    pub async fn import_leaf_aggregation_job_queue(
+        &mut self,
+        records: &[LeafAggregationJobRecord],
+    ) {
+        for record in records {
+            let status = match record.status.as_str() {
+                "in_progress" => "queued",
+                status => status,
+            };
+            sqlx::query!(
+                "
+                    INSERT INTO leaf_aggregation_witness_jobs_fri
+                        (l1_batch_number, circuit_id, closed_form_inputs_blob_url, number_of_basic_circuits, proof_availability_bitmap, job_label, status, attempts, created_at, updated_at)
+                    VALUES ($1, $2, $3, $4, $5::text::varbit, $6, $7, $8, now(), now())
+                    ON CONFLICT(l1_batch_number, circuit_id) DO NOTHING
+                    ",
+                record.block_number.0 as i64,
+                record.circuit_id as i16,
+                record.closed_form_inputs_blob_url.as_deref(),
+                record.number_of_basic_circuits.map(|count| count as i32),
+                record.proof_availability_bitmap.as_deref(),
+                record.job_label.as_deref(),
+                status,
+                record.attempts as i16,
+            )
+            .execute(self.storage.conn())
+            .await
+            .unwrap();
+        }
+    }
+
+    /// Marks the basic circuit proof with the given `sequence_number` as available in the bitmap of
+    /// the leaf aggregation job waiting for it. Bits are indexed by the position of the proof among
+    /// the basic circuit prover jobs of the same circuit. Returns the id of the leaf aggregation job,
+    /// or `None` if there is no such job or its bitmap doesn't cover the proof (e.g., the job was
+    /// created before bitmaps were introduced); the bitmap is informational, so this isn't an error.
+    pub async fn mark_leaf_aggregation_proof_available(
+        &mut self,
+        block_number: L1BatchNumber,
+        circuit_id: u8,
+        sequence_number: usize,
+    ) -> Option<u32> {
+        sqlx::query!(
+            "
+                WITH proof AS (
+                    SELECT COUNT(*)::int AS bit_index
+                    FROM prover_jobs_fri
+                    WHERE l1_batch_number = $1
+                      AND circuit_id = $2
+                      AND aggregation_round = 0
+                      AND sequence_number < $3
+                )
+                UPDATE leaf_aggregation_witness_jobs_fri
+                SET proof_availability_bitmap = set_bit(proof_availability_bitmap, proof.bit_index, 1),
+                    updated_at = now()
+                FROM proof
+                WHERE l1_batch_number = $1 AND circuit_id = $2
+                  AND proof.bit_index < bit_length(proof_availability_bitmap)
+                RETURNING id
+                ",
+            block_number.0 as i64,
+            circuit_id as i16,
+            sequence_number as i32,
+        )
+        .fetch_optional(self.storage.conn())
+        .await
+        .unwrap()
+        .map(|row| row.id as u32)
+    }
+
     pub async fn move_leaf_aggregation_jobs_from_waiting_to_queued(&mut self) -> Vec<(i64, u8)> {
         sqlx::query!(
                 r#"
@@ -376,23 +681,37 @@ impl FriWitnessGeneratorDal<'_, '_> {
         .unwrap();
     }
 
-    pub async fn get_next_node_aggregation_job(&mut self) -> Option<NodeAggregationJobMetadata> {
+    pub async fn get_next_node_aggregation_job(
+        &mut self,
+        circuit_ids: Option<&[u8]>,
+    ) -> Option<NodeAggregationJobMetadata> {
+        let circuit_ids: Option<Vec<i16>> =
+            circuit_ids.map(|ids| ids.iter().map(|&id| id as i16).collect());
         let row = sqlx::query!(
             "
                 UPDATE node_aggregation_witness_jobs_fri
                 SET status = 'in_progress', attempts = attempts + 1,
                     updated_at = now(), processing_started_at = now()
                 WHERE id = (
-                    SELECT id
-                    FROM node_aggregation_witness_jobs_fri
-                    WHERE status = 'queued'
-                    ORDER BY l1_batch_number ASC, depth ASC, id ASC
+                    SELECT nawj.id
+                    FROM node_aggregation_witness_jobs_fri nawj
+                    WHERE nawj.status = 'queued'
+                    AND ($1::smallint[] IS NULL OR nawj.circuit_id = ANY($1))
+                    AND NOT EXISTS (
+                        SELECT 1
+                        FROM node_aggregation_job_dependencies_fri deps
+                        JOIN prover_jobs_fri ON prover_jobs_fri.id = deps.prover_job_id
+                        WHERE deps.node_aggregation_job_id = nawj.id
+                        AND prover_jobs_fri.status != 'successful'
+                    )
+                    ORDER BY nawj.l1_batch_number ASC, nawj.depth ASC, nawj.id ASC
                     LIMIT 1
                     FOR UPDATE
                     SKIP LOCKED
                 )
                 RETURNING node_aggregation_witness_jobs_fri.*
                 ",
+            circuit_ids.as_deref(),
         )
         .fetch_optional(self.storage.conn())
         .await
@@ -416,9 +735,41 @@ impl FriWitnessGeneratorDal<'_, '_> {
             circuit_id: row.circuit_id as u8,
             depth,
             prover_job_ids_for_proofs: prover_job_ids,
+            aggregations_url: row.aggregations_url,
+            created_at: DateTime::<Utc>::from_utc(row.created_at, Utc),
         })
     }
 
+    /// Returns the delay between the last leaf aggregation job of the batch completing and the first
+    /// node aggregation job of the batch being claimed. Returns `None` once more than a single node
+    /// aggregation job of the batch was claimed, so that the latency is reported once per batch.
+    pub async fn get_round_handoff_latency(
+        &mut self,
+        block_number: L1BatchNumber,
+    ) -> Option<Duration> {
+        let latency_seconds = sqlx::query!(
+            r#"
+                SELECT EXTRACT(EPOCH FROM (
+                    MIN(node.processing_started_at) - (
+                        SELECT MAX(completed_at)
+                        FROM leaf_aggregation_witness_jobs_fri
+                        WHERE l1_batch_number = $1 AND status = 'successful'
+                    )
+                ))::FLOAT8 AS "latency_seconds"
+                FROM node_aggregation_witness_jobs_fri node
+                WHERE node.l1_batch_number = $1 AND node.processing_started_at IS NOT NULL
+                HAVING COUNT(*) = 1
+                "#,
+            block_number.0 as i64,
+        )
+        .fetch_optional(self.storage.conn())
+        .await
+        .unwrap()?
+        .latency_seconds?;
+        // Node jobs of a circuit may be picked before leaf jobs of other circuits completed.
+        Some(Duration::from_secs_f64(latency_seconds.max(0.0)))
+    }
+
     pub async fn mark_node_aggregation_job_failed(&mut self, error: &str, id: u32) {
         sqlx::query!(
             "
@@ -473,6 +824,58 @@ impl FriWitnessGeneratorDal<'_, '_> {
             .unwrap();
     }
 
+    /// Records the prover jobs producing the proofs aggregated by the node aggregation job
+    /// as its dependencies; the job isn't claimed until all of them are successful.
+    pub async fn insert_node_aggregation_job_dependencies(
+        &mut self,
+        block_number: L1BatchNumber,
+        circuit_id: u8,
+        depth: u16,
+    ) {
+        sqlx::query!(
+            "
+                INSERT INTO node_aggregation_job_dependencies_fri (node_aggregation_job_id, prover_job_id, created_at)
+                SELECT nawj.id, prover_jobs_fri.id, now()
+                FROM node_aggregation_witness_jobs_fri nawj
+                JOIN prover_jobs_fri ON prover_jobs_fri.l1_batch_number = nawj.l1_batch_number
+                    AND prover_jobs_fri.circuit_id = nawj.circuit_id
+                    AND prover_jobs_fri.depth = nawj.depth
+                    AND prover_jobs_fri.aggregation_round = CASE WHEN nawj.depth = 0 THEN 1 ELSE 2 END
+                WHERE nawj.l1_batch_number = $1 AND nawj.circuit_id = $2 AND nawj.depth = $3
+                ON CONFLICT DO NOTHING
+                ",
+            block_number.0 as i64,
+            circuit_id as i16,
+            depth as i32,
+        )
+        .execute(self.storage.conn())
+        .await
+        .unwrap();
+    }
+
+    /// Returns the maximum depth of node aggregation jobs created for the batch.
+    pub async fn get_max_node_aggregation_depth(
+        &mut self,
+        block_number: L1BatchNumber,
+    ) -> Option<u16> {
+        sqlx::query!(
+            "
+                SELECT MAX(depth) AS max_depth
+                FROM node_aggregation_witness_jobs_fri
+                WHERE l1_batch_number = $1
+                ",
+            block_number.0 as i64,
+        )
+        .fetch_one(self.storage.conn())
+        .await
+        .unwrap()
+        .max_depth
+        .map(|depth| depth as u16)
+    }
+
+    /// Queues depth 0 node aggregation jobs whose leaf aggregation job is successful and whose
+    /// proofs are all ready. The leaf aggregation job sets `number_of_dependent_jobs` of its node
+    /// aggregation job in the same transaction that marks it successful.
     pub async fn move_depth_zero_node_aggregation_jobs(&mut self) -> Vec<(i64, u8, u16)> {
         sqlx::query!(
                 r#"
@@ -486,6 +889,7 @@ impl FriWitnessGeneratorDal<'_, '_> {
                                 AND prover_jobs_fri.circuit_id = nawj.circuit_id
                                 AND prover_jobs_fri.depth = nawj.depth
                        WHERE nawj.status = 'waiting_for_proofs'
+                         AND nawj.number_of_dependent_jobs IS NOT NULL
                          AND prover_jobs_fri.status = 'successful'
                          AND prover_jobs_fri.aggregation_round = 1
                          AND prover_jobs_fri.depth = 0
@@ -621,8 +1025,8 @@ impl FriWitnessGeneratorDal<'_, '_> {
             .collect()
     }
 
-    pub async fn get_next_scheduler_witness_job(&mut self) -> Option<L1BatchNumber> {
-        let result: Option<L1BatchNumber> = sqlx::query!(
+    pub async fn get_next_scheduler_witness_job(&mut self) -> Option<SchedulerWitnessJobMetadata> {
+        let result: Option<SchedulerWitnessJobMetadata> = sqlx::query!(
             "
                 UPDATE scheduler_witness_jobs_fri
                 SET status = 'in_progress', attempts = attempts + 1,
@@ -642,7 +1046,10 @@ impl FriWitnessGeneratorDal<'_, '_> {
         .fetch_optional(self.storage.conn())
         .await
         .unwrap()
-        .map(|row| L1BatchNumber(row.l1_batch_number as u32));
+        .map(|row| SchedulerWitnessJobMetadata {
+            block_number: L1BatchNumber(row.l1_batch_number as u32),
+            created_at: DateTime::<Utc>::from_utc(row.created_at, Utc),
+        });
         result
     }
 
@@ -117,6 +117,17 @@ impl<'a> StorageProcessor<'a> {
         processor
     }
 
+    /// Starts a transaction in which Postgres rejects all writes, e.g. for checks that must not
+    /// affect the state of the database.
+    pub async fn start_read_only_transaction<'c: 'b, 'b>(&'c mut self) -> StorageProcessor<'b> {
+        let mut processor = self.start_transaction().await;
+        sqlx::query("SET TRANSACTION READ ONLY")
+            .execute(processor.conn())
+            .await
+            .unwrap();
+        processor
+    }
+
     /// Checks if the `StorageProcessor` is currently within database transaction.
     pub fn in_transaction(&self) -> bool {
         self.in_transaction
@@ -2,6 +2,7 @@ use std::fs;
 use std::time::Duration;
 
 use db_test_macro::db_test;
+use zksync_config::configs::fri_witness_generator::JobOrdering;
 use zksync_contracts::BaseSystemContractsHashes;
 use zksync_types::{
     block::{L1BatchHeader, MiniblockHeader},
@@ -23,6 +24,7 @@ use crate::transactions_dal::L2TxSubmissionResult;
 use crate::transactions_dal::TransactionsDal;
 use crate::transactions_web3_dal::TransactionsWeb3Dal;
 use crate::witness_generator_dal::WitnessGeneratorDal;
+use crate::StorageProcessor;
 
 const DEFAULT_GAS_PER_PUBDATA: u32 = 100;
 
@@ -545,6 +547,694 @@ async fn test_move_scheduler_jobs_from_waiting_to_queued(connection_pool: Connec
     assert_eq!(l1_batch_number, job.unwrap().block_number);
 }
 
+#[db_test(dal_crate)]
+async fn test_transition_fri_prover_job_state(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let block_number = 1;
+    let header = L1BatchHeader::new(
+        L1BatchNumber(block_number),
+        0,
+        Default::default(),
+        Default::default(),
+    );
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+
+    let mut prover_dal = storage.fri_prover_jobs_dal();
+    prover_dal
+        .insert_prover_job(
+            L1BatchNumber(block_number),
+            1,
+            0,
+            0,
+            AggregationRound::BasicCircuits,
+            "1_0_Main VM_BasicCircuits.bin",
+            false,
+        )
+        .await;
+    let job = prover_dal.get_next_job().await.unwrap();
+
+    // the job is `in_progress` now, so a transition from `queued` must be rejected
+    assert!(
+        !prover_dal
+            .transition_job_state(job.id, "queued", "sent_to_external")
+            .await
+    );
+    assert!(
+        prover_dal
+            .transition_job_state(job.id, "in_progress", "sent_to_external")
+            .await
+    );
+    assert!(
+        prover_dal
+            .transition_job_state(job.id, "sent_to_external", "awaiting_import")
+            .await
+    );
+}
+
+#[db_test(dal_crate)]
+async fn test_prover_jobs_are_inserted_in_batch(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let block_number = 1;
+    let header = L1BatchHeader::new(
+        L1BatchNumber(block_number),
+        0,
+        Default::default(),
+        Default::default(),
+    );
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+
+    let circuit_ids_and_urls: Vec<_> = (0..50)
+        .map(|i| {
+            let circuit_id = (i % 13 + 1) as u8;
+            (
+                circuit_id,
+                format!("1_{i}_{circuit_id}_LeafAggregation.bin"),
+            )
+        })
+        .collect();
+    storage
+        .fri_prover_jobs_dal()
+        .insert_prover_jobs(
+            L1BatchNumber(block_number),
+            circuit_ids_and_urls.clone(),
+            AggregationRound::LeafAggregation,
+            0,
+        )
+        .await;
+
+    let rows: Vec<(i16, String, i16, i32, i32, bool, String)> = sqlx::query_as(
+        "SELECT circuit_id, circuit_blob_url, aggregation_round, sequence_number, depth, \
+         is_node_final_proof, status FROM prover_jobs_fri ORDER BY sequence_number",
+    )
+    .fetch_all(storage.conn())
+    .await
+    .unwrap();
+    assert_eq!(rows.len(), circuit_ids_and_urls.len());
+    for (sequence_number, (row, (circuit_id, circuit_blob_url))) in
+        rows.into_iter().zip(circuit_ids_and_urls).enumerate()
+    {
+        let expected = (
+            circuit_id as i16,
+            circuit_blob_url,
+            AggregationRound::LeafAggregation as i16,
+            sequence_number as i32,
+            0,
+            false,
+            "queued".to_owned(),
+        );
+        assert_eq!(row, expected);
+    }
+}
+
+/// Mirrors the witness generator mapping of base layer circuit ids to the ids of their leaf circuits,
+/// which follow the scheduler (1) and node (2) recursive layer circuit ids.
+fn base_circuit_id_to_leaf_circuit_id(circuit_id: u8) -> u8 {
+    circuit_id + 2
+}
+
+/// Creates aggregation jobs of the batch for the given `(circuit_id, number_of_basic_circuits)` pairs.
+async fn create_aggregation_jobs(
+    storage: &mut StorageProcessor<'_>,
+    l1_batch_number: L1BatchNumber,
+    circuits: &[(u8, usize)],
+) {
+    let closed_form_inputs_and_urls: Vec<_> = circuits
+        .iter()
+        .map(|&(circuit_id, number_of_basic_circuits)| {
+            let url = format!(
+                "closed_form_inputs_{}_{}.bin",
+                l1_batch_number.0, circuit_id
+            );
+            (circuit_id, url, number_of_basic_circuits)
+        })
+        .collect();
+    storage
+        .fri_witness_generator_dal()
+        .create_aggregation_jobs(
+            l1_batch_number,
+            &closed_form_inputs_and_urls,
+            &format!("scheduler_witness_{}.bin", l1_batch_number.0),
+            base_circuit_id_to_leaf_circuit_id,
+        )
+        .await;
+}
+
+/// Creates a circuit 1 leaf aggregation job with a single basic circuit for each of the batches.
+async fn create_leaf_aggregation_jobs(storage: &mut StorageProcessor<'_>, block_numbers: &[u32]) {
+    for &block_number in block_numbers {
+        create_aggregation_jobs(storage, L1BatchNumber(block_number), &[(1, 1)]).await;
+    }
+}
+
+/// Queues all leaf aggregation jobs regardless of their proofs, so that they can be claimed.
+async fn queue_leaf_aggregation_jobs(storage: &mut StorageProcessor<'_>) {
+    sqlx::query("UPDATE leaf_aggregation_witness_jobs_fri SET status = 'queued'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+}
+
+#[db_test(dal_crate)]
+async fn test_leaf_aggregation_proof_availability_bitmap(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let block_number = 1;
+    let header = L1BatchHeader::new(
+        L1BatchNumber(block_number),
+        0,
+        Default::default(),
+        Default::default(),
+    );
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+
+    let l1_batch_number = L1BatchNumber(block_number);
+    // Sequence numbers are shared by all circuits of the batch, so circuit 1 gets 0 and 2.
+    storage
+        .fri_prover_jobs_dal()
+        .insert_prover_jobs(
+            l1_batch_number,
+            vec![
+                (1, "1_0_Main VM_BasicCircuits.bin".to_owned()),
+                (2, "1_1_SHA256_BasicCircuits.bin".to_owned()),
+                (1, "1_2_Main VM_BasicCircuits.bin".to_owned()),
+            ],
+            AggregationRound::BasicCircuits,
+            0,
+        )
+        .await;
+    create_aggregation_jobs(storage, l1_batch_number, &[(1, 2), (2, 1)]).await;
+
+    let id = storage
+        .fri_witness_generator_dal()
+        .mark_leaf_aggregation_proof_available(l1_batch_number, 1, 0)
+        .await
+        .unwrap();
+    assert_eq!(
+        proof_availability_bitmap(storage, id).await.as_deref(),
+        Some("10")
+    );
+    storage
+        .fri_witness_generator_dal()
+        .mark_leaf_aggregation_proof_available(l1_batch_number, 1, 2)
+        .await;
+    assert_eq!(
+        proof_availability_bitmap(storage, id).await.as_deref(),
+        Some("11")
+    );
+
+    // A proof past the end of the bitmap is skipped rather than failing the update.
+    let marked = storage
+        .fri_witness_generator_dal()
+        .mark_leaf_aggregation_proof_available(l1_batch_number, 1, 3)
+        .await;
+    assert_eq!(marked, None);
+    assert_eq!(
+        proof_availability_bitmap(storage, id).await.as_deref(),
+        Some("11")
+    );
+
+    // So are jobs without a bitmap, e.g. ones created before bitmaps were introduced.
+    sqlx::query("UPDATE leaf_aggregation_witness_jobs_fri SET proof_availability_bitmap = NULL")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    let marked = storage
+        .fri_witness_generator_dal()
+        .mark_leaf_aggregation_proof_available(l1_batch_number, 1, 0)
+        .await;
+    assert_eq!(marked, None);
+    assert_eq!(proof_availability_bitmap(storage, id).await, None);
+}
+
+async fn proof_availability_bitmap(storage: &mut StorageProcessor<'_>, id: u32) -> Option<String> {
+    let (bitmap,): (Option<String>,) = sqlx::query_as(
+        "SELECT proof_availability_bitmap::text FROM leaf_aggregation_witness_jobs_fri WHERE id = $1",
+    )
+    .bind(id as i64)
+    .fetch_one(storage.conn())
+    .await
+    .unwrap();
+    bitmap
+}
+
+#[db_test(dal_crate)]
+async fn test_leaf_aggregation_jobs_with_equal_priority_are_claimed_in_id_order(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let block_number = 1;
+    let header = L1BatchHeader::new(
+        L1BatchNumber(block_number),
+        0,
+        Default::default(),
+        Default::default(),
+    );
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+
+    let l1_batch_number = L1BatchNumber(block_number);
+    let mut prover_dal = storage.fri_prover_jobs_dal();
+    prover_dal
+        .insert_prover_jobs(
+            l1_batch_number,
+            vec![
+                (1, "1_0_Main VM_BasicCircuits.bin".to_owned()),
+                (2, "1_1_SHA256_BasicCircuits.bin".to_owned()),
+            ],
+            AggregationRound::BasicCircuits,
+            0,
+        )
+        .await;
+    while let Some(job) = prover_dal.get_next_job().await {
+        prover_dal
+            .save_proof(job.id, Duration::from_secs(0), "unit-test")
+            .await;
+    }
+
+    create_aggregation_jobs(storage, l1_batch_number, &[(1, 1), (2, 1)]).await;
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    witness_generator_dal
+        .move_leaf_aggregation_jobs_from_waiting_to_queued()
+        .await;
+
+    // Both jobs belong to the same L1 batch, so only the job id decides the claim order.
+    let first_job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    let second_job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert!(first_job.id < second_job.id);
+    assert_eq!((first_job.circuit_id.0, second_job.circuit_id.0), (1, 2));
+}
+
+#[db_test(dal_crate)]
+async fn test_leaf_aggregation_jobs_are_claimed_in_configured_order(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    for ordering in [JobOrdering::OldestBlockFirst, JobOrdering::Fifo] {
+        sqlx::query("DELETE FROM leaf_aggregation_witness_jobs_fri")
+            .execute(storage.conn())
+            .await
+            .unwrap();
+        // Jobs are created out of order, e.g., as when a batch is re-run during incident recovery.
+        create_leaf_aggregation_jobs(storage, &[3, 1, 2]).await;
+        queue_leaf_aggregation_jobs(storage).await;
+
+        let mut witness_generator_dal = storage.fri_witness_generator_dal();
+        let mut claimed_batches = vec![];
+        while let Some(job) = witness_generator_dal
+            .get_next_leaf_aggregation_job(None, None, None, None, ordering)
+            .await
+        {
+            claimed_batches.push(job.block_number.0);
+        }
+        let expected_batches = match ordering {
+            JobOrdering::OldestBlockFirst => [1, 2, 3],
+            JobOrdering::Fifo => [3, 1, 2],
+        };
+        assert_eq!(claimed_batches, expected_batches, "{ordering:?}");
+    }
+}
+
+#[db_test(dal_crate)]
+async fn test_leaf_aggregation_jobs_outside_of_batch_range_are_not_claimed(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_leaf_aggregation_jobs(storage, &[99, 100, 200, 201]).await;
+    queue_leaf_aggregation_jobs(storage).await;
+
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    let batch_range = L1BatchNumber(100)..=L1BatchNumber(200);
+    let mut claimed_batches = vec![];
+    while let Some(job) = witness_generator_dal
+        .get_next_leaf_aggregation_job(
+            None,
+            Some(batch_range.clone()),
+            None,
+            None,
+            JobOrdering::OldestBlockFirst,
+        )
+        .await
+    {
+        claimed_batches.push(job.block_number);
+    }
+    assert_eq!(claimed_batches, [L1BatchNumber(100), L1BatchNumber(200)]);
+}
+
+#[db_test(dal_crate)]
+async fn test_labeled_leaf_aggregation_jobs_are_claimed_by_matching_workers(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_leaf_aggregation_jobs(storage, &[1, 2, 3]).await;
+    storage
+        .fri_witness_generator_dal()
+        .label_leaf_aggregation_jobs(L1BatchNumber(2)..=L1BatchNumber(2), "experiment-A")
+        .await;
+    queue_leaf_aggregation_jobs(storage).await;
+
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    let labels = ["experiment-A".to_owned()];
+    let mut claimed_batches = vec![];
+    while let Some(job) = witness_generator_dal
+        .get_next_leaf_aggregation_job(
+            None,
+            None,
+            Some(&labels),
+            None,
+            JobOrdering::OldestBlockFirst,
+        )
+        .await
+    {
+        claimed_batches.push(job.block_number);
+    }
+    assert_eq!(claimed_batches, [L1BatchNumber(2)]);
+
+    let mut claimed_batches = vec![];
+    while let Some(job) = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+    {
+        claimed_batches.push(job.block_number);
+    }
+    assert_eq!(claimed_batches, [L1BatchNumber(1), L1BatchNumber(3)]);
+}
+
+#[db_test(dal_crate)]
+async fn test_failed_leaf_aggregation_job_is_not_reclaimed_during_backoff(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_leaf_aggregation_jobs(storage, &[1]).await;
+    queue_leaf_aggregation_jobs(storage).await;
+
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    let retry_backoff = Some(Duration::from_secs(3600));
+    let job = witness_generator_dal
+        .get_next_leaf_aggregation_job(
+            None,
+            None,
+            None,
+            retry_backoff,
+            JobOrdering::OldestBlockFirst,
+        )
+        .await
+        .unwrap();
+    witness_generator_dal
+        .mark_leaf_aggregation_job_failed("unit-test", job.id, 10)
+        .await;
+    witness_generator_dal
+        .requeue_stuck_leaf_aggregations_jobs(Duration::from_secs(3600), 10)
+        .await;
+
+    assert!(witness_generator_dal
+        .get_next_leaf_aggregation_job(
+            None,
+            None,
+            None,
+            retry_backoff,
+            JobOrdering::OldestBlockFirst
+        )
+        .await
+        .is_none());
+    let retried_job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert_eq!(retried_job.id, job.id);
+}
+
+#[db_test(dal_crate)]
+async fn test_leaf_aggregation_job_exceeding_max_attempts_is_not_requeued(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_leaf_aggregation_jobs(storage, &[1]).await;
+    queue_leaf_aggregation_jobs(storage).await;
+
+    let max_attempts = 2;
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    let job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert_eq!(job.attempts, 1);
+    witness_generator_dal
+        .mark_leaf_aggregation_job_failed("unit-test", job.id, max_attempts)
+        .await;
+    let requeued = witness_generator_dal
+        .requeue_stuck_leaf_aggregations_jobs(Duration::from_secs(3600), max_attempts)
+        .await;
+    assert_eq!(requeued.len(), 1);
+
+    let job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert!(job.attempts >= max_attempts);
+    witness_generator_dal
+        .mark_leaf_aggregation_job_failed("unit-test", job.id, max_attempts)
+        .await;
+    let requeued = witness_generator_dal
+        .requeue_stuck_leaf_aggregations_jobs(Duration::from_secs(0), u32::MAX)
+        .await;
+    assert!(requeued.is_empty());
+    assert!(witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .is_none());
+
+    let (status,): (String,) =
+        sqlx::query_as("SELECT status FROM leaf_aggregation_witness_jobs_fri")
+            .fetch_one(storage.conn())
+            .await
+            .unwrap();
+    assert_eq!(status, "max_attempts_exceeded");
+}
+
+#[db_test(dal_crate)]
+async fn test_failed_leaf_aggregation_jobs_are_requeued(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_leaf_aggregation_jobs(storage, &[1, 2]).await;
+    queue_leaf_aggregation_jobs(storage).await;
+
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    while let Some(job) = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+    {
+        witness_generator_dal
+            .mark_leaf_aggregation_job_failed("unit-test", job.id, 10)
+            .await;
+    }
+
+    let requeued = witness_generator_dal
+        .requeue_failed_leaf_aggregation_jobs(Some(L1BatchNumber(2)), None)
+        .await;
+    assert_eq!(requeued, 1);
+    let job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert_eq!(job.block_number, L1BatchNumber(2));
+    assert_eq!(job.attempts, 1);
+    assert!(witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .is_none());
+
+    // Jobs that aren't failed are left intact.
+    let requeued = witness_generator_dal
+        .requeue_failed_leaf_aggregation_jobs(None, Some(job.id))
+        .await;
+    assert_eq!(requeued, 0);
+    let (error,): (Option<String>,) =
+        sqlx::query_as("SELECT error FROM leaf_aggregation_witness_jobs_fri WHERE id = $1")
+            .bind(job.id as i64)
+            .fetch_one(storage.conn())
+            .await
+            .unwrap();
+    assert_eq!(error, None);
+}
+
+#[db_test(dal_crate)]
+async fn test_queued_leaf_aggregation_jobs_are_listed_without_picking(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_leaf_aggregation_jobs(storage, &[2, 1]).await;
+    assert!(storage
+        .fri_witness_generator_dal()
+        .get_queued_leaf_aggregation_jobs()
+        .await
+        .is_empty());
+    queue_leaf_aggregation_jobs(storage).await;
+
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    let jobs = witness_generator_dal
+        .get_queued_leaf_aggregation_jobs()
+        .await;
+    let block_numbers: Vec<_> = jobs.iter().map(|job| job.block_number).collect();
+    assert_eq!(block_numbers, [L1BatchNumber(1), L1BatchNumber(2)]);
+    assert!(jobs.iter().all(|job| job.attempts == 0));
+
+    // Listed jobs can still be picked.
+    let job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert_eq!(job.id, jobs[0].id);
+    assert_eq!(
+        witness_generator_dal
+            .get_queued_leaf_aggregation_jobs()
+            .await
+            .len(),
+        1
+    );
+}
+
+#[db_test(dal_crate)]
+async fn test_max_node_aggregation_depth(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert_eq!(
+        witness_generator_dal
+            .get_max_node_aggregation_depth(L1BatchNumber(1))
+            .await,
+        None
+    );
+
+    // Circuit 3 of batch 1 recurses two levels deep; batch 2 shouldn't affect batch 1.
+    let jobs = [(1, 3, 0), (1, 3, 1), (1, 3, 2), (1, 4, 0), (2, 3, 5)];
+    for (block_number, circuit_id, depth) in jobs {
+        witness_generator_dal
+            .insert_node_aggregation_jobs(L1BatchNumber(block_number), circuit_id, None, depth, "")
+            .await;
+    }
+    assert_eq!(
+        witness_generator_dal
+            .get_max_node_aggregation_depth(L1BatchNumber(1))
+            .await,
+        Some(2)
+    );
+}
+
+#[db_test(dal_crate)]
+async fn test_export_and_import_leaf_aggregation_job_queue(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    create_aggregation_jobs(storage, L1BatchNumber(1), &[(1, 2), (2, 1)]).await;
+    sqlx::query(
+        "UPDATE leaf_aggregation_witness_jobs_fri SET proof_availability_bitmap = B'10' \
+         WHERE circuit_id = 1",
+    )
+    .execute(storage.conn())
+    .await
+    .unwrap();
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    witness_generator_dal
+        .label_leaf_aggregation_jobs(L1BatchNumber(1)..=L1BatchNumber(1), "dr-drill")
+        .await;
+    let records = witness_generator_dal
+        .export_leaf_aggregation_job_queue()
+        .await;
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].circuit_id, 1);
+    assert_eq!(records[0].number_of_basic_circuits, Some(2));
+    assert_eq!(records[0].proof_availability_bitmap.as_deref(), Some("10"));
+    assert_eq!(records[0].job_label.as_deref(), Some("dr-drill"));
+    assert_eq!(records[0].status, "waiting_for_proofs");
+    assert_eq!(records[1].proof_availability_bitmap.as_deref(), Some("0"));
+
+    // Simulate a rebuilt environment.
+    sqlx::query("DELETE FROM leaf_aggregation_witness_jobs_fri")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert!(witness_generator_dal
+        .export_leaf_aggregation_job_queue()
+        .await
+        .is_empty());
+
+    witness_generator_dal
+        .import_leaf_aggregation_job_queue(&records)
+        .await;
+    assert_eq!(
+        witness_generator_dal
+            .export_leaf_aggregation_job_queue()
+            .await,
+        records
+    );
+}
+
+#[db_test(dal_crate)]
+async fn test_round_handoff_latency(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let l1_batch_number = L1BatchNumber(1);
+    create_aggregation_jobs(storage, l1_batch_number, &[(1, 1), (2, 1)]).await;
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert_eq!(
+        witness_generator_dal
+            .get_round_handoff_latency(l1_batch_number)
+            .await,
+        None
+    );
+
+    sqlx::query(
+        "UPDATE leaf_aggregation_witness_jobs_fri SET status = 'successful', \
+         completed_at = CASE circuit_id WHEN 1 THEN '2023-07-01 10:00:00'::timestamp \
+         ELSE '2023-07-01 10:01:00'::timestamp END",
+    )
+    .execute(storage.conn())
+    .await
+    .unwrap();
+    sqlx::query(
+        "UPDATE node_aggregation_witness_jobs_fri \
+         SET processing_started_at = '2023-07-01 10:01:30' WHERE circuit_id = 3",
+    )
+    .execute(storage.conn())
+    .await
+    .unwrap();
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert_eq!(
+        witness_generator_dal
+            .get_round_handoff_latency(l1_batch_number)
+            .await,
+        Some(Duration::from_secs(30))
+    );
+
+    // The latency is only reported when the first node aggregation job of the batch is claimed.
+    sqlx::query(
+        "UPDATE node_aggregation_witness_jobs_fri \
+         SET processing_started_at = '2023-07-01 10:02:00' WHERE circuit_id = 4",
+    )
+    .execute(storage.conn())
+    .await
+    .unwrap();
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert_eq!(
+        witness_generator_dal
+            .get_round_handoff_latency(l1_batch_number)
+            .await,
+        None
+    );
+}
+
 fn get_default_prover_jobs_params(l1_batch_number: L1BatchNumber) -> GetProverJobsParams {
     GetProverJobsParams {
         statuses: None,
@@ -563,3 +1253,266 @@ fn get_sample_proof() -> Vec<u8> {
     fs::read(format!("{}/etc/prover-test-data/proof.bin", zksync_home))
         .expect("Failed reading test proof file")
 }
+
+#[db_test(dal_crate)]
+async fn test_maintenance_lock_is_held_until_expiry(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let ttl = Duration::from_secs(60);
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert!(
+        witness_generator_dal
+            .try_acquire_maintenance_lock("requeue_stuck_jobs", ttl)
+            .await
+    );
+    assert!(
+        !witness_generator_dal
+            .try_acquire_maintenance_lock("requeue_stuck_jobs", ttl)
+            .await
+    );
+    // Locks of different tasks are independent.
+    assert!(
+        witness_generator_dal
+            .try_acquire_maintenance_lock("gcs_blob_cleaner", ttl)
+            .await
+    );
+
+    // Simulates the lease running out.
+    sqlx::query(
+        "UPDATE maintenance_locks SET locked_until = now() - interval '1 second' WHERE name = 'requeue_stuck_jobs'",
+    )
+    .execute(storage.conn())
+    .await
+    .unwrap();
+    assert!(
+        storage
+            .fri_witness_generator_dal()
+            .try_acquire_maintenance_lock("requeue_stuck_jobs", ttl)
+            .await
+    );
+}
+
+#[db_test(dal_crate)]
+async fn test_node_aggregation_job_waits_for_dependencies(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let l1_batch_number = L1BatchNumber(1);
+    let header = L1BatchHeader::new(l1_batch_number, 0, Default::default(), Default::default());
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+    storage
+        .fri_prover_jobs_dal()
+        .insert_prover_jobs(
+            l1_batch_number,
+            vec![
+                (3, "1_0_3_LeafAggregation.bin".to_owned()),
+                (3, "1_1_3_LeafAggregation.bin".to_owned()),
+            ],
+            AggregationRound::LeafAggregation,
+            0,
+        )
+        .await;
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    witness_generator_dal
+        .insert_node_aggregation_jobs(l1_batch_number, 3, Some(2), 0, "aggregations_1_3_0.bin")
+        .await;
+    witness_generator_dal
+        .insert_node_aggregation_job_dependencies(l1_batch_number, 3, 0)
+        .await;
+
+    // Simulates the job being queued while one of its proofs isn't ready yet.
+    sqlx::query("UPDATE node_aggregation_witness_jobs_fri SET status = 'queued'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE prover_jobs_fri SET status = 'successful' WHERE sequence_number = 0")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    assert!(storage
+        .fri_witness_generator_dal()
+        .get_next_node_aggregation_job(None)
+        .await
+        .is_none());
+
+    sqlx::query("UPDATE prover_jobs_fri SET status = 'successful'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    // Jobs of circuits outside of the allowlist (e.g., of drained workers) aren't picked.
+    for circuit_ids in [&[4][..], &[]] {
+        assert!(storage
+            .fri_witness_generator_dal()
+            .get_next_node_aggregation_job(Some(circuit_ids))
+            .await
+            .is_none());
+    }
+    let job = storage
+        .fri_witness_generator_dal()
+        .get_next_node_aggregation_job(Some(&[3, 4]))
+        .await
+        .unwrap();
+    assert_eq!(job.circuit_id, 3);
+    assert_eq!(job.prover_job_ids_for_proofs.len(), 2);
+}
+
+#[db_test(dal_crate)]
+async fn test_leaf_aggregation_job_with_missing_artifacts_is_requeued(
+    connection_pool: ConnectionPool,
+) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let l1_batch_number = L1BatchNumber(1);
+    let header = L1BatchHeader::new(l1_batch_number, 0, Default::default(), Default::default());
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+    create_leaf_aggregation_jobs(storage, &[l1_batch_number.0]).await;
+    // Simulates the leaf aggregation job having succeeded, its proof being generated,
+    // and its dependent node job being claimed.
+    storage
+        .fri_prover_jobs_dal()
+        .insert_prover_jobs(
+            l1_batch_number,
+            vec![(3, "1_0_3_LeafAggregation.bin".to_owned())],
+            AggregationRound::LeafAggregation,
+            0,
+        )
+        .await;
+    sqlx::query("UPDATE prover_jobs_fri SET status = 'successful'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    storage
+        .fri_witness_generator_dal()
+        .update_node_aggregation_jobs_url(l1_batch_number, 3, 1, 0, "aggregations_1_3_0.bin".into())
+        .await;
+    sqlx::query("UPDATE leaf_aggregation_witness_jobs_fri SET status = 'successful'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    let (node_job_id,): (i64,) = sqlx::query_as(
+        "UPDATE node_aggregation_witness_jobs_fri SET status = 'in_progress' RETURNING id",
+    )
+    .fetch_one(storage.conn())
+    .await
+    .unwrap();
+
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert!(
+        witness_generator_dal
+            .requeue_leaf_aggregation_job_with_missing_artifacts(
+                l1_batch_number,
+                1,
+                node_job_id as u32
+            )
+            .await
+    );
+    // The node job waits for the leaf aggregation job, even though its proofs are ready.
+    assert!(witness_generator_dal
+        .move_depth_zero_node_aggregation_jobs()
+        .await
+        .is_empty());
+    let job = witness_generator_dal
+        .get_next_leaf_aggregation_job(None, None, None, None, JobOrdering::OldestBlockFirst)
+        .await
+        .unwrap();
+    assert_eq!(job.circuit_id.0, 1);
+
+    // The leaf aggregation job is already being reprocessed, so neither job is changed.
+    sqlx::query("UPDATE node_aggregation_witness_jobs_fri SET status = 'in_progress'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    assert!(
+        !witness_generator_dal
+            .requeue_leaf_aggregation_job_with_missing_artifacts(
+                l1_batch_number,
+                1,
+                node_job_id as u32
+            )
+            .await
+    );
+    let (status,): (String,) =
+        sqlx::query_as("SELECT status FROM node_aggregation_witness_jobs_fri WHERE id = $1")
+            .bind(node_job_id)
+            .fetch_one(storage.conn())
+            .await
+            .unwrap();
+    assert_eq!(status, "in_progress");
+
+    // Once the rerun succeeds, the node job is queued again.
+    sqlx::query("UPDATE node_aggregation_witness_jobs_fri SET status = 'waiting_for_proofs'")
+        .execute(storage.conn())
+        .await
+        .unwrap();
+    let mut witness_generator_dal = storage.fri_witness_generator_dal();
+    witness_generator_dal
+        .update_node_aggregation_jobs_url(l1_batch_number, 3, 1, 0, "aggregations_1_3_0.bin".into())
+        .await;
+    witness_generator_dal
+        .mark_leaf_aggregation_as_successful(job.id, Duration::from_secs(0))
+        .await;
+    assert_eq!(
+        witness_generator_dal
+            .move_depth_zero_node_aggregation_jobs()
+            .await,
+        [(1, 3, 0)]
+    );
+}
+
+#[db_test(dal_crate)]
+async fn test_circuit_presigned_urls_are_stored_on_prover_jobs(connection_pool: ConnectionPool) {
+    let storage = &mut connection_pool.access_test_storage().await;
+    let l1_batch_number = L1BatchNumber(1);
+    let header = L1BatchHeader::new(l1_batch_number, 0, Default::default(), Default::default());
+    storage
+        .blocks_dal()
+        .insert_l1_batch(&header, Default::default())
+        .await;
+    storage
+        .fri_prover_jobs_dal()
+        .insert_prover_jobs(
+            l1_batch_number,
+            vec![
+                (3, "1_0_3_LeafAggregation_0.bin".to_owned()),
+                (3, "1_0_3_LeafAggregation_1.bin".to_owned()),
+            ],
+            AggregationRound::LeafAggregation,
+            0,
+        )
+        .await;
+    let ids: Vec<(i64,)> =
+        sqlx::query_as("SELECT id FROM prover_jobs_fri ORDER BY sequence_number")
+            .fetch_all(storage.conn())
+            .await
+            .unwrap();
+
+    let mut prover_dal = storage.fri_prover_jobs_dal();
+    assert_eq!(
+        prover_dal.get_circuit_presigned_url(ids[0].0 as u32).await,
+        None
+    );
+    prover_dal
+        .set_circuit_presigned_urls(
+            l1_batch_number,
+            AggregationRound::LeafAggregation,
+            vec![(
+                "1_0_3_LeafAggregation_1.bin".to_owned(),
+                "https://example.com/1_0_3_LeafAggregation_1.bin?sig".to_owned(),
+            )],
+        )
+        .await;
+    assert_eq!(
+        prover_dal.get_circuit_presigned_url(ids[0].0 as u32).await,
+        None
+    );
+    assert_eq!(
+        prover_dal
+            .get_circuit_presigned_url(ids[1].0 as u32)
+            .await
+            .as_deref(),
+        Some("https://example.com/1_0_3_LeafAggregation_1.bin?sig")
+    );
+}
@@ -0,0 +1,276 @@
+//! Object store wrapper caching fetched blobs in memory, so that blobs read repeatedly
+//! (e.g., by node aggregation jobs for the same block) are only fetched once.
+
+use async_trait::async_trait;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
+
+type CacheKey = (Bucket, String);
+
+#[derive(Debug)]
+struct CacheEntry {
+    value: Vec<u8>,
+    last_used: u64,
+}
+
+/// Least recently used cache of blobs capped by their total size.
+#[derive(Debug)]
+struct LruCache {
+    max_bytes: u64,
+    total_bytes: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Keys of the entries ordered by the last access.
+    recency: BTreeMap<u64, CacheKey>,
+    next_tick: u64,
+}
+
+impl LruCache {
+    fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            total_bytes: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>> {
+        let tick = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(tick, key.clone());
+        entry.last_used = tick;
+        Some(entry.value.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, value: Vec<u8>) {
+        let size = value.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        self.remove(&key);
+        while self.total_bytes + size > self.max_bytes {
+            let Some((_, evicted_key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&evicted_key) {
+                self.total_bytes -= evicted.value.len() as u64;
+            }
+        }
+        let tick = self.tick();
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                last_used: tick,
+            },
+        );
+        self.total_bytes += size;
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.total_bytes -= entry.value.len() as u64;
+        }
+    }
+}
+
+/// [`ObjectStore`] memoizing fetched blobs in a least recently used cache of up to `max_cache_bytes`.
+/// Writes and removals are always forwarded to the wrapped store and evict the affected keys.
+#[derive(Debug)]
+pub struct CachingObjectStore {
+    inner: Box<dyn ObjectStore>,
+    cache: Mutex<LruCache>,
+}
+
+impl CachingObjectStore {
+    pub fn new(inner: Box<dyn ObjectStore>, max_cache_bytes: u64) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(max_cache_bytes)),
+        }
+    }
+
+    fn cache(&self) -> MutexGuard<'_, LruCache> {
+        self.cache.lock().expect("object store cache is poisoned")
+    }
+}
+
+#[async_trait]
+impl ObjectStore for CachingObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        let cache_key = (bucket, key.to_owned());
+        if let Some(value) = self.cache().get(&cache_key) {
+            return Ok(value);
+        }
+        let value = self.inner.get_raw(bucket, key).await?;
+        self.cache().insert(cache_key, value.clone());
+        Ok(value)
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        // The entry is evicted after the write as well, so that a read racing with the write
+        // doesn't keep serving the replaced value.
+        let cache_key = (bucket, key.to_owned());
+        self.cache().remove(&cache_key);
+        let result = self.inner.put_raw(bucket, key, value).await;
+        self.cache().remove(&cache_key);
+        result
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        let cache_key = (bucket, key.to_owned());
+        self.cache().remove(&cache_key);
+        let result = self.inner.remove_raw(bucket, key).await;
+        self.cache().remove(&cache_key);
+        result
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        let result = self.inner.remove_many_raw(bucket, keys).await;
+        let mut cache = self.cache();
+        for key in keys {
+            cache.remove(&(bucket, (*key).to_owned()));
+        }
+        result
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        self.inner.get_presigned_url(bucket, key, ttl).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ObjectStoreFactory;
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Store counting the reads forwarded to the wrapped store.
+    #[derive(Debug)]
+    struct CountingStore {
+        inner: Box<dyn ObjectStore>,
+        reads: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl ObjectStore for CountingStore {
+        async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_raw(bucket, key).await
+        }
+
+        async fn put_raw(
+            &self,
+            bucket: Bucket,
+            key: &str,
+            value: Vec<u8>,
+        ) -> Result<(), ObjectStoreError> {
+            self.inner.put_raw(bucket, key, value).await
+        }
+
+        async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+            self.inner.remove_raw(bucket, key).await
+        }
+    }
+
+    async fn caching_store(max_cache_bytes: u64) -> (CachingObjectStore, Arc<AtomicUsize>) {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let inner = CountingStore {
+            inner: ObjectStoreFactory::mock().create_store().await,
+            reads: reads.clone(),
+        };
+        let store = CachingObjectStore::new(Box::new(inner), max_cache_bytes);
+        (store, reads)
+    }
+
+    #[tokio::test]
+    async fn repeated_gets_are_served_from_cache() {
+        let (store, reads) = caching_store(100).await;
+        store
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![1; 10])
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let value = store
+                .get_raw(Bucket::ProverJobs, "test-key.bin")
+                .await
+                .unwrap();
+            assert_eq!(value, [1; 10]);
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+        store
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![2; 10])
+            .await
+            .unwrap();
+        let value = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert_eq!(value, [2; 10]);
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+        store
+            .remove_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        let err = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_blobs_are_evicted() {
+        let (store, reads) = caching_store(20).await;
+        for key in ["a.bin", "b.bin", "c.bin"] {
+            store
+                .put_raw(Bucket::ProverJobs, key, vec![0; 10])
+                .await
+                .unwrap();
+        }
+
+        store.get_raw(Bucket::ProverJobs, "a.bin").await.unwrap();
+        store.get_raw(Bucket::ProverJobs, "b.bin").await.unwrap();
+        store.get_raw(Bucket::ProverJobs, "a.bin").await.unwrap();
+        // Evicts `b.bin`, which wasn't used for the longest time.
+        store.get_raw(Bucket::ProverJobs, "c.bin").await.unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+
+        store.get_raw(Bucket::ProverJobs, "a.bin").await.unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+        store.get_raw(Bucket::ProverJobs, "b.bin").await.unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,187 @@
+//! Object store wrapper storing a checksum alongside every blob, so that bit-rot or truncated
+//! uploads are detected on load rather than surfacing as confusing deserialization errors.
+
+use async_trait::async_trait;
+use zksync_config::configs::object_store::ContentHashAlgorithm;
+
+use std::time::Duration;
+
+use crate::objects::digest;
+use crate::raw::{Bucket, IntegrityError, ObjectStore, ObjectStoreError};
+
+/// [`ObjectStore`] writing the checksum of each blob to a sibling object named after the hash
+/// algorithm (e.g., `{key}.sha256`) and checking fetched blobs against it. Blobs without
+/// a checksum of the configured algorithm (e.g., ones written before the checksums were enabled
+/// or the algorithm was changed) are returned unchecked.
+#[derive(Debug)]
+pub struct ChecksummedObjectStore {
+    inner: Box<dyn ObjectStore>,
+    algorithm: ContentHashAlgorithm,
+}
+
+impl ChecksummedObjectStore {
+    pub fn new(inner: Box<dyn ObjectStore>, algorithm: ContentHashAlgorithm) -> Self {
+        Self { inner, algorithm }
+    }
+
+    fn checksum_key(&self, key: &str) -> String {
+        let extension = match self.algorithm {
+            ContentHashAlgorithm::Sha256 => "sha256",
+            ContentHashAlgorithm::Blake2b => "blake2b",
+        };
+        format!("{key}.{extension}")
+    }
+
+    fn checksum(&self, bytes: &[u8]) -> String {
+        hex::encode(digest(bytes, self.algorithm))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for ChecksummedObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        let bytes = self.inner.get_raw(bucket, key).await?;
+        let expected = match self.inner.get_raw(bucket, &self.checksum_key(key)).await {
+            Ok(expected) => String::from_utf8_lossy(&expected).into_owned(),
+            Err(ObjectStoreError::KeyNotFound(_)) => return Ok(bytes),
+            Err(err) => return Err(err),
+        };
+        let actual = self.checksum(&bytes);
+        if actual != expected {
+            return Err(ObjectStoreError::Integrity(IntegrityError {
+                key: key.to_owned(),
+                expected,
+                actual,
+            }));
+        }
+        Ok(bytes)
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        let checksum = self.checksum(&value);
+        self.inner.put_raw(bucket, key, value).await?;
+        self.inner
+            .put_raw(bucket, &self.checksum_key(key), checksum.into_bytes())
+            .await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.inner.remove_raw(bucket, key).await?;
+        match self.inner.remove_raw(bucket, &self.checksum_key(key)).await {
+            Ok(()) | Err(ObjectStoreError::KeyNotFound(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        let checksum_keys: Vec<_> = keys.iter().map(|key| self.checksum_key(key)).collect();
+        self.inner.remove_many_raw(bucket, keys).await?;
+        let checksum_keys: Vec<_> = checksum_keys.iter().map(String::as_str).collect();
+        self.inner.remove_many_raw(bucket, &checksum_keys).await
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        self.inner.get_presigned_url(bucket, key, ttl).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ObjectStoreFactory;
+
+    #[tokio::test]
+    async fn blobs_are_checked_against_checksums() {
+        let factory = ObjectStoreFactory::mock();
+        let store =
+            ChecksummedObjectStore::new(factory.create_store().await, ContentHashAlgorithm::Sha256);
+        store
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![1, 2, 3])
+            .await
+            .unwrap();
+        let value = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert_eq!(value, [1, 2, 3]);
+
+        // Simulate a truncated upload by overwriting the blob directly.
+        let raw_store = factory.create_store().await;
+        raw_store
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![1, 2])
+            .await
+            .unwrap();
+        let err = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap_err();
+        let ObjectStoreError::Integrity(err) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(err.key, "test-key.bin");
+        assert_eq!(err.expected, store.checksum(&[1, 2, 3]));
+        assert_eq!(err.actual, store.checksum(&[1, 2]));
+    }
+
+    #[tokio::test]
+    async fn checksums_use_configured_algorithm() {
+        let factory = ObjectStoreFactory::mock();
+        let store = ChecksummedObjectStore::new(
+            factory.create_store().await,
+            ContentHashAlgorithm::Blake2b,
+        );
+        store
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        let raw_store = factory.create_store().await;
+        let checksum = raw_store
+            .get_raw(Bucket::ProverJobs, "test-key.bin.blake2b")
+            .await
+            .unwrap();
+        assert_eq!(
+            checksum,
+            hex::encode(digest(&[1, 2, 3], ContentHashAlgorithm::Blake2b)).into_bytes()
+        );
+        let err = raw_store
+            .get_raw(Bucket::ProverJobs, "test-key.bin.sha256")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)), "{err}");
+        let value = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert_eq!(value, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn blobs_without_checksums_are_loaded() {
+        let factory = ObjectStoreFactory::mock();
+        factory
+            .create_store()
+            .await
+            .put_raw(Bucket::ProverJobs, "legacy-key.bin", vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        let store =
+            ChecksummedObjectStore::new(factory.create_store().await, ContentHashAlgorithm::Sha256);
+        let value = store
+            .get_raw(Bucket::ProverJobs, "legacy-key.bin")
+            .await
+            .unwrap();
+        assert_eq!(value, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,137 @@
+//! Object store wrapper compressing blobs with zstd, so that large artifacts (e.g., recursive layer
+//! prover inputs and node aggregations) take less storage and egress.
+
+use async_trait::async_trait;
+
+use std::time::Duration;
+
+use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
+
+/// Header prepended to compressed blobs: magic bytes followed by the format version.
+const HEADER: [u8; 5] = [b'Z', b'K', b'Z', b'S', 1];
+
+/// [`ObjectStore`] compressing blobs on `put` if a compression level is set and decompressing
+/// fetched blobs starting with [`HEADER`]. Blobs without the header (e.g., ones written before
+/// the compression was enabled) are returned as is, so reads work regardless of the level.
+/// Presigned URLs point to the stored blobs, so whoever fetches them must handle the header.
+#[derive(Debug)]
+pub struct CompressedObjectStore {
+    inner: Box<dyn ObjectStore>,
+    level: Option<i32>,
+}
+
+impl CompressedObjectStore {
+    pub fn new(inner: Box<dyn ObjectStore>, level: Option<i32>) -> Self {
+        Self { inner, level }
+    }
+}
+
+fn compress(value: &[u8], level: i32) -> Result<Vec<u8>, ObjectStoreError> {
+    let mut compressed = HEADER.to_vec();
+    zstd::stream::copy_encode(value, &mut compressed, level)
+        .map_err(|err| ObjectStoreError::Serialization(err.into()))?;
+    Ok(compressed)
+}
+
+fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, ObjectStoreError> {
+    match bytes.strip_prefix(&HEADER) {
+        Some(compressed) => zstd::stream::decode_all(compressed)
+            .map_err(|err| ObjectStoreError::Serialization(err.into())),
+        None => Ok(bytes),
+    }
+}
+
+#[async_trait]
+impl ObjectStore for CompressedObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        decompress(self.inner.get_raw(bucket, key).await?)
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        let Some(level) = self.level else {
+            return self.inner.put_raw(bucket, key, value).await;
+        };
+        let compressed = compress(&value, level)?;
+        #[allow(clippy::cast_precision_loss)] // precision loss is acceptable for a metric
+        let compression_ratio = value.len() as f64 / compressed.len() as f64;
+        metrics::histogram!(
+            "prover_fri.object_store.compression_ratio",
+            compression_ratio,
+            "bucket" => bucket.as_str()
+        );
+        // Incompressible blobs are stored as is; they're still readable without the header.
+        if compressed.len() < value.len() {
+            self.inner.put_raw(bucket, key, compressed).await
+        } else {
+            self.inner.put_raw(bucket, key, value).await
+        }
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.inner.remove_raw(bucket, key).await
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        self.inner.remove_many_raw(bucket, keys).await
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        self.inner.get_presigned_url(bucket, key, ttl).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ObjectStoreFactory;
+
+    #[tokio::test]
+    async fn blobs_are_compressed_and_legacy_blobs_are_loaded() {
+        let factory = ObjectStoreFactory::mock();
+        let store = CompressedObjectStore::new(factory.create_store().await, Some(3));
+        let value = vec![7; 4_096];
+        store
+            .put_raw(Bucket::ProverJobsFri, "compressed.bin", value.clone())
+            .await
+            .unwrap();
+
+        let raw_store = factory.create_store().await;
+        let stored = raw_store
+            .get_raw(Bucket::ProverJobsFri, "compressed.bin")
+            .await
+            .unwrap();
+        assert!(stored.starts_with(&HEADER));
+        assert!(stored.len() < value.len());
+        let loaded = store
+            .get_raw(Bucket::ProverJobsFri, "compressed.bin")
+            .await
+            .unwrap();
+        assert_eq!(loaded, value);
+
+        raw_store
+            .put_raw(Bucket::ProverJobsFri, "legacy.bin", vec![1, 2, 3])
+            .await
+            .unwrap();
+        let uncompressing_store = CompressedObjectStore::new(factory.create_store().await, None);
+        let loaded = uncompressing_store
+            .get_raw(Bucket::ProverJobsFri, "legacy.bin")
+            .await
+            .unwrap();
+        assert_eq!(loaded, [1, 2, 3]);
+        let loaded = uncompressing_store
+            .get_raw(Bucket::ProverJobsFri, "compressed.bin")
+            .await
+            .unwrap();
+        assert_eq!(loaded, value);
+    }
+}
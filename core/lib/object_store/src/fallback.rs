@@ -0,0 +1,120 @@
+//! Object store wrapper reading blobs missing from the primary store from a secondary one,
+//! so that artifacts written before a store migration stay readable.
+
+use async_trait::async_trait;
+
+use std::time::Duration;
+
+use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
+
+/// [`ObjectStore`] writing to, removing from and presigning URLs with the primary store only. Blobs not found
+/// in the primary store are fetched from the secondary one; other errors of the primary store
+/// are surfaced as is.
+#[derive(Debug)]
+pub struct FallbackObjectStore {
+    primary: Box<dyn ObjectStore>,
+    secondary: Box<dyn ObjectStore>,
+}
+
+impl FallbackObjectStore {
+    pub fn new(primary: Box<dyn ObjectStore>, secondary: Box<dyn ObjectStore>) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FallbackObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        match self.primary.get_raw(bucket, key).await {
+            Err(ObjectStoreError::KeyNotFound(_)) => {
+                let value = self.secondary.get_raw(bucket, key).await?;
+                metrics::increment_counter!(
+                    "prover_fri.object_store.fallback_reads",
+                    "bucket" => bucket.as_str()
+                );
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        self.primary.put_raw(bucket, key, value).await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.primary.remove_raw(bucket, key).await
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        self.primary.remove_many_raw(bucket, keys).await
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        self.primary.get_presigned_url(bucket, key, ttl).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ObjectStoreFactory;
+
+    #[tokio::test]
+    async fn blobs_missing_from_primary_store_are_read_from_secondary() {
+        let primary_factory = ObjectStoreFactory::mock();
+        let secondary_factory = ObjectStoreFactory::mock();
+        let secondary = secondary_factory.create_store().await;
+        secondary
+            .put_raw(Bucket::ProofsFri, "old.bin", vec![1])
+            .await
+            .unwrap();
+        let store = FallbackObjectStore::new(
+            primary_factory.create_store().await,
+            secondary_factory.create_store().await,
+        );
+
+        let value = store.get_raw(Bucket::ProofsFri, "old.bin").await.unwrap();
+        assert_eq!(value, [1]);
+        let err = store
+            .get_raw(Bucket::ProofsFri, "missing.bin")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)), "{err}");
+
+        store
+            .put_raw(Bucket::ProofsFri, "new.bin", vec![2])
+            .await
+            .unwrap();
+        let primary = primary_factory.create_store().await;
+        assert_eq!(
+            primary.get_raw(Bucket::ProofsFri, "new.bin").await.unwrap(),
+            [2]
+        );
+        let err = secondary
+            .get_raw(Bucket::ProofsFri, "new.bin")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)), "{err}");
+
+        // Removal never touches the blobs of the secondary store.
+        store
+            .remove_raw(Bucket::ProofsFri, "old.bin")
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_raw(Bucket::ProofsFri, "old.bin").await.unwrap(),
+            [1]
+        );
+    }
+}
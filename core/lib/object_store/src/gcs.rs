@@ -1,6 +1,7 @@
 //! GCS-based [`ObjectStore`] implementation.
 
 use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
 use google_cloud_auth::{credentials::CredentialsFile, error::Error};
 use google_cloud_storage::{
     client::{Client, ClientConfig},
@@ -11,6 +12,7 @@ use google_cloud_storage::{
         upload::{Media, UploadObjectRequest, UploadType},
     },
     http::Error as HttpError,
+    sign::SignedURLOptions,
 };
 use http::StatusCode;
 
@@ -22,6 +24,9 @@ use std::{
 
 use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
 
+/// Max number of delete requests sent concurrently by [`GoogleCloudStorage::remove_many_raw()`].
+const MAX_CONCURRENT_REMOVALS: usize = 16;
+
 async fn retry<T, E, Fut, F>(max_retries: u16, mut f: F) -> Result<T, E>
 where
     Fut: Future<Output = Result<T, E>>,
@@ -215,6 +220,45 @@ impl ObjectStore for GoogleCloudStorage {
     async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
         self.remove_inner(bucket.as_str(), key).await
     }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        let started_at = Instant::now();
+        let results: Vec<_> = stream::iter(keys)
+            .map(|key| self.remove_inner(bucket.as_str(), key))
+            .buffer_unordered(MAX_CONCURRENT_REMOVALS)
+            .collect()
+            .await;
+        vlog::trace!(
+            "Removed {} keys from GCS bucket {bucket} and it took: {:?}",
+            keys.len(),
+            started_at.elapsed()
+        );
+        for result in results {
+            match result {
+                Ok(()) | Err(ObjectStoreError::KeyNotFound(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        let filename = Self::filename(bucket.as_str(), key);
+        let options = SignedURLOptions {
+            expires: ttl,
+            ..SignedURLOptions::default()
+        };
+        // The service account of the client credentials signs the URL.
+        self.client
+            .signed_url(&self.bucket_prefix, &filename, None, None, options)
+            .await
+            .map_err(|err| ObjectStoreError::Other(err.into()))
+    }
 }
 
 #[cfg(test)]
@@ -255,4 +299,48 @@ mod test {
         let result = retry(2, || retry_success_after_n_retries(2)).await;
         assert_eq!(result, Ok(42));
     }
+
+    /// Requires GCS credentials and the `GCS_TEST_BUCKET` variable.
+    #[cfg(feature = "gcs-tests")]
+    #[tokio::test]
+    async fn many_objects_are_removed_at_once() {
+        let bucket_prefix = std::env::var("GCS_TEST_BUCKET").expect("GCS_TEST_BUCKET is not set");
+        let store = GoogleCloudStorage::new(None, bucket_prefix, 1).await;
+        let keys: Vec<_> = (0..40).map(|i| format!("remove_many_{i}.bin")).collect();
+        for key in &keys {
+            store
+                .put_raw(Bucket::ProverJobsFri, key, vec![1, 2, 3])
+                .await
+                .unwrap();
+        }
+
+        let mut keys: Vec<_> = keys.iter().map(String::as_str).collect();
+        keys.push("remove_many_missing.bin");
+        store
+            .remove_many_raw(Bucket::ProverJobsFri, &keys)
+            .await
+            .unwrap();
+        for key in &keys {
+            let err = store.get_raw(Bucket::ProverJobsFri, key).await.unwrap_err();
+            assert!(matches!(err, ObjectStoreError::KeyNotFound(_)), "{err}");
+        }
+    }
+
+    /// Requires GCS credentials of a service account and the `GCS_TEST_BUCKET` variable.
+    #[cfg(feature = "gcs-tests")]
+    #[tokio::test]
+    async fn presigned_url_points_to_object() {
+        let bucket_prefix = std::env::var("GCS_TEST_BUCKET").expect("GCS_TEST_BUCKET is not set");
+        let store = GoogleCloudStorage::new(None, bucket_prefix.clone(), 1).await;
+        let url = store
+            .get_presigned_url(Bucket::ProverJobsFri, "1.bin", Duration::from_secs(600))
+            .await
+            .unwrap();
+
+        let expected_prefix =
+            format!("https://storage.googleapis.com/{bucket_prefix}/prover_jobs_fri/1.bin?");
+        assert!(url.starts_with(&expected_prefix), "{url}");
+        assert!(url.contains("X-Goog-Expires=600"), "{url}");
+        assert!(url.contains("X-Goog-Signature="), "{url}");
+    }
 }
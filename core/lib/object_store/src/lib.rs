@@ -23,11 +23,17 @@
     clippy::doc_markdown
 )]
 
+mod caching;
+mod checksummed;
+mod compressed;
+mod fallback;
 mod file;
 mod gcs;
+mod metered;
 mod mock;
 mod objects;
 mod raw;
+mod retry;
 
 // Re-export `bincode` crate so that client binaries can conveniently use it.
 pub use bincode;
@@ -38,6 +44,12 @@ pub mod _reexports {
 }
 
 pub use self::{
+    caching::CachingObjectStore,
+    checksummed::ChecksummedObjectStore,
+    compressed::CompressedObjectStore,
+    fallback::FallbackObjectStore,
+    metered::{EgressMeter, MeteredObjectStore},
     objects::{AggregationsKey, CircuitKey, ClosedFormInputKey, FriCircuitKey, StoredObject},
-    raw::{Bucket, ObjectStore, ObjectStoreError, ObjectStoreFactory},
+    raw::{Bucket, IntegrityError, ObjectStore, ObjectStoreError, ObjectStoreFactory},
+    retry::RetryableObjectStore,
 };
@@ -0,0 +1,157 @@
+//! Object store wrapper metering egress, so that workers can cap the amount of data
+//! they transfer from the store.
+
+use async_trait::async_trait;
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
+
+#[derive(Debug)]
+struct EgressWindow {
+    started_at: Instant,
+    bytes: u64,
+}
+
+/// Meter of the bytes fetched from an object store within a fixed time window.
+/// Once the window elapses, the metered amount is reset.
+#[derive(Debug)]
+pub struct EgressMeter {
+    max_bytes_per_window: u64,
+    window: Duration,
+    state: Mutex<EgressWindow>,
+}
+
+impl EgressMeter {
+    const HOUR: Duration = Duration::from_secs(3_600);
+
+    /// Creates a meter allowing to fetch `max_bytes_per_hour` bytes per hour.
+    pub fn hourly(max_bytes_per_hour: u64) -> Self {
+        Self::new(max_bytes_per_hour, Self::HOUR)
+    }
+
+    fn new(max_bytes_per_window: u64, window: Duration) -> Self {
+        Self {
+            max_bytes_per_window,
+            window,
+            state: Mutex::new(EgressWindow {
+                started_at: Instant::now(),
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// Checks whether the egress budget for the current window is exhausted.
+    pub fn is_exhausted(&self) -> bool {
+        self.is_exhausted_at(Instant::now())
+    }
+
+    fn is_exhausted_at(&self, now: Instant) -> bool {
+        self.current_window(now).bytes >= self.max_bytes_per_window
+    }
+
+    fn record(&self, bytes: u64, now: Instant) {
+        self.current_window(now).bytes += bytes;
+    }
+
+    fn current_window(&self, now: Instant) -> MutexGuard<'_, EgressWindow> {
+        let mut state = self.state.lock().expect("egress meter is poisoned");
+        if now.saturating_duration_since(state.started_at) >= self.window {
+            *state = EgressWindow {
+                started_at: now,
+                bytes: 0,
+            };
+        }
+        state
+    }
+}
+
+/// [`ObjectStore`] recording the size of all fetched blobs in an [`EgressMeter`].
+#[derive(Debug)]
+pub struct MeteredObjectStore {
+    inner: Box<dyn ObjectStore>,
+    meter: Arc<EgressMeter>,
+}
+
+impl MeteredObjectStore {
+    pub fn new(inner: Box<dyn ObjectStore>, meter: Arc<EgressMeter>) -> Self {
+        Self { inner, meter }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MeteredObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        let bytes = self.inner.get_raw(bucket, key).await?;
+        self.meter.record(bytes.len() as u64, Instant::now());
+        Ok(bytes)
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        self.inner.put_raw(bucket, key, value).await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.inner.remove_raw(bucket, key).await
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        self.inner.remove_many_raw(bucket, keys).await
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        self.inner.get_presigned_url(bucket, key, ttl).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ObjectStoreFactory;
+
+    #[tokio::test]
+    async fn meter_is_exhausted_once_egress_crosses_cap() {
+        let meter = Arc::new(EgressMeter::hourly(10));
+        let store = MeteredObjectStore::new(
+            ObjectStoreFactory::mock().create_store().await,
+            meter.clone(),
+        );
+        store
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![0; 6])
+            .await
+            .unwrap();
+
+        store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert!(!meter.is_exhausted());
+        store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert!(meter.is_exhausted());
+    }
+
+    #[test]
+    fn meter_resets_in_next_window() {
+        let meter = EgressMeter::new(10, Duration::from_secs(60));
+        let started_at = Instant::now();
+        meter.record(10, started_at);
+        assert!(meter.is_exhausted_at(started_at + Duration::from_secs(59)));
+        assert!(!meter.is_exhausted_at(started_at + Duration::from_secs(60)));
+    }
+}
@@ -1,5 +1,8 @@
 //! Stored objects.
 
+use blake2::Blake2b512;
+use sha2::{Digest, Sha256};
+use zksync_config::configs::object_store::ContentHashAlgorithm;
 use zksync_types::{
     proofs::{AggregationRound, PrepareBasicCircuitsJob},
     zkevm_test_harness::{
@@ -14,7 +17,7 @@ use zksync_types::{
     L1BatchNumber,
 };
 
-use crate::raw::{BoxedError, Bucket, ObjectStore, ObjectStoreError};
+use crate::raw::{BoxedError, Bucket, IntegrityError, ObjectStore, ObjectStoreError};
 
 /// Object that can be stored in an [`ObjectStore`].
 pub trait StoredObject: Sized {
@@ -218,4 +221,119 @@ impl dyn ObjectStore + '_ {
         self.put_raw(V::BUCKET, &key, bytes).await?;
         Ok(key)
     }
+
+    /// Same as [`Self::put()`], but appends a short hash of the serialized value to the key.
+    /// Distinct contents of the same logical object thus get distinct keys, which can be cached
+    /// indefinitely (e.g., by a CDN fronting the store). The hash algorithm is recorded in the key,
+    /// so that keys produced with different algorithms can coexist. The returned key should be
+    /// persisted and used with [`Self::get_by_encoded_key()`] or
+    /// [`Self::get_verified_by_content_key()`] to fetch the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or the insertion / replacement operation fails.
+    pub async fn put_with_content_hash<V: StoredObject>(
+        &self,
+        key: V::Key<'_>,
+        value: &V,
+        algorithm: ContentHashAlgorithm,
+    ) -> Result<String, ObjectStoreError> {
+        let bytes = value.serialize().map_err(ObjectStoreError::Serialization)?;
+        let key = with_content_hash_suffix(&V::encode_key(key), &bytes, algorithm);
+        self.put_raw(V::BUCKET, &key, bytes).await?;
+        Ok(key)
+    }
+
+    /// Fetches the value by a key previously returned from [`Self::put()`]
+    /// or [`Self::put_with_content_hash()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an object with the `encoded_key` does not exist, cannot be accessed,
+    /// or cannot be deserialized.
+    pub async fn get_by_encoded_key<V: StoredObject>(
+        &self,
+        encoded_key: &str,
+    ) -> Result<V, ObjectStoreError> {
+        let bytes = self.get_raw(V::BUCKET, encoded_key).await?;
+        V::deserialize(bytes).map_err(ObjectStoreError::Serialization)
+    }
+
+    /// Same as [`Self::get_by_encoded_key()`] for keys returned from [`Self::put_with_content_hash()`],
+    /// but also checks that the fetched blob matches the content hash in the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the object cannot be fetched or deserialized, or
+    /// [`ObjectStoreError::Integrity`] if the key has no content hash or the content doesn't match it.
+    pub async fn get_verified_by_content_key<V: StoredObject>(
+        &self,
+        content_key: &str,
+    ) -> Result<V, ObjectStoreError> {
+        let (algorithm, expected_hash) =
+            parse_content_hash_suffix(content_key).ok_or_else(|| {
+                ObjectStoreError::Integrity(IntegrityError {
+                    key: content_key.to_owned(),
+                    expected: "a content hash in the key".to_owned(),
+                    actual: "none".to_owned(),
+                })
+            })?;
+        let bytes = self.get_raw(V::BUCKET, content_key).await?;
+        let actual_hash = content_hash(&bytes, algorithm);
+        if actual_hash != expected_hash {
+            let prefix = content_hash_prefix(algorithm);
+            return Err(ObjectStoreError::Integrity(IntegrityError {
+                key: content_key.to_owned(),
+                expected: format!("{prefix}{expected_hash}"),
+                actual: format!("{prefix}{actual_hash}"),
+            }));
+        }
+        V::deserialize(bytes).map_err(ObjectStoreError::Serialization)
+    }
+}
+
+const CONTENT_HASH_ALGORITHMS: [ContentHashAlgorithm; 2] =
+    [ContentHashAlgorithm::Sha256, ContentHashAlgorithm::Blake2b];
+
+/// Prefix of content hashes distinguishing the algorithm, so that a content hash suffix
+/// can't be confused with a key ending in 8 hex digits.
+fn content_hash_prefix(algorithm: ContentHashAlgorithm) -> &'static str {
+    match algorithm {
+        ContentHashAlgorithm::Sha256 => "s256-",
+        ContentHashAlgorithm::Blake2b => "b2-",
+    }
+}
+
+pub(crate) fn digest(bytes: &[u8], algorithm: ContentHashAlgorithm) -> Vec<u8> {
+    match algorithm {
+        ContentHashAlgorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+        ContentHashAlgorithm::Blake2b => Blake2b512::digest(bytes).to_vec(),
+    }
+}
+
+/// Returns the first 4 bytes of the digest of `bytes` in hex.
+fn content_hash(bytes: &[u8], algorithm: ContentHashAlgorithm) -> String {
+    hex::encode(&digest(bytes, algorithm)[..4])
+}
+
+/// Inserts the content hash of `bytes` before the key extension,
+/// e.g. `closed_form_inputs_1_2.bin` -> `closed_form_inputs_1_2_b2-0a1b2c3d.bin`.
+fn with_content_hash_suffix(key: &str, bytes: &[u8], algorithm: ContentHashAlgorithm) -> String {
+    let prefix = content_hash_prefix(algorithm);
+    let hash = content_hash(bytes, algorithm);
+    match key.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}_{prefix}{hash}.{extension}"),
+        None => format!("{key}_{prefix}{hash}"),
+    }
+}
+
+fn parse_content_hash_suffix(key: &str) -> Option<(ContentHashAlgorithm, &str)> {
+    let stem = key.rsplit_once('.').map_or(key, |(stem, _)| stem);
+    let (_, suffix) = stem.rsplit_once('_')?;
+    let (algorithm, hash) = CONTENT_HASH_ALGORITHMS.into_iter().find_map(|algorithm| {
+        let hash = suffix.strip_prefix(content_hash_prefix(algorithm))?;
+        Some((algorithm, hash))
+    })?;
+    let is_hash = hash.len() == 8 && hash.bytes().all(|byte| byte.is_ascii_hexdigit());
+    is_hash.then_some((algorithm, hash))
 }
@@ -1,10 +1,17 @@
 use async_trait::async_trait;
 
-use std::{error, fmt, sync::Arc};
+use tokio::sync::Mutex;
 
-use crate::{file::FileBackedObjectStore, gcs::GoogleCloudStorage, mock::MockStore};
-use zksync_config::configs::object_store::ObjectStoreMode;
+use std::{collections::HashMap, error, fmt, sync::Arc, time::Duration};
+
+use crate::{
+    caching::CachingObjectStore, checksummed::ChecksummedObjectStore,
+    compressed::CompressedObjectStore, fallback::FallbackObjectStore, file::FileBackedObjectStore,
+    gcs::GoogleCloudStorage, mock::MockStore, retry::RetryableObjectStore,
+};
+use zksync_config::configs::object_store::{ContentHashAlgorithm, ObjectStoreMode};
 use zksync_config::ObjectStoreConfig;
+use zksync_types::proofs::AggregationRound;
 
 /// Bucket for [`ObjectStore`] in which objects can be placed.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -57,14 +64,40 @@ pub enum ObjectStoreError {
     Serialization(BoxedError),
     /// Other error has occurred when accessing the store (e.g., a network error).
     Other(BoxedError),
+    /// A blob doesn't match the checksum stored alongside it.
+    Integrity(IntegrityError),
+    /// The operation isn't supported by the store (e.g., presigned URLs for the local filesystem).
+    Unsupported(&'static str),
+}
+
+/// Mismatch between a fetched blob and its stored checksum.
+#[derive(Debug)]
+pub struct IntegrityError {
+    pub key: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "checksum mismatch for {}: expected {}, got {}",
+            self.key, self.expected, self.actual
+        )
+    }
 }
 
+impl error::Error for IntegrityError {}
+
 impl fmt::Display for ObjectStoreError {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::KeyNotFound(err) => write!(formatter, "key not found: {err}"),
             Self::Serialization(err) => write!(formatter, "serialization error: {err}"),
             Self::Other(err) => write!(formatter, "other error: {err}"),
+            Self::Integrity(err) => write!(formatter, "integrity error: {err}"),
+            Self::Unsupported(operation) => write!(formatter, "unsupported operation: {operation}"),
         }
     }
 }
@@ -75,6 +108,8 @@ impl error::Error for ObjectStoreError {
             Self::KeyNotFound(err) | Self::Serialization(err) | Self::Other(err) => {
                 Some(err.as_ref())
             }
+            Self::Integrity(err) => Some(err),
+            Self::Unsupported(_) => None,
         }
     }
 }
@@ -114,6 +149,39 @@ pub trait ObjectStore: fmt::Debug + Send + Sync {
     ///
     /// Returns an error if removal fails.
     async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError>;
+
+    /// Removes the values associated with the keys from the given bucket. Keys that don't exist
+    /// are skipped. By default, the keys are removed one by one; stores supporting batch removal
+    /// should override this method.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error other than [`ObjectStoreError::KeyNotFound`].
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        for key in keys {
+            match self.remove_raw(bucket, key).await {
+                Ok(()) | Err(ObjectStoreError::KeyNotFound(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a URL through which the value for the given key can be fetched without credentials
+    /// until `ttl` elapses. The key isn't checked to exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ObjectStoreError::Unsupported`] if the store can't generate such URLs, or an error
+    /// if signing the URL fails.
+    async fn get_presigned_url(
+        &self,
+        _bucket: Bucket,
+        _key: &str,
+        _ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        Err(ObjectStoreError::Unsupported("presigned URLs"))
+    }
 }
 
 #[async_trait]
@@ -134,6 +202,19 @@ impl<T: ObjectStore + ?Sized> ObjectStore for Arc<T> {
     async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
         (**self).remove_raw(bucket, key).await
     }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        (**self).remove_many_raw(bucket, keys).await
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        (**self).get_presigned_url(bucket, key, ttl).await
+    }
 }
 
 #[derive(Debug)]
@@ -146,6 +227,11 @@ enum ObjectStoreOrigin {
 #[derive(Debug)]
 pub struct ObjectStoreFactory {
     origin: ObjectStoreOrigin,
+    /// Configs of the stores used instead of the default one by specific aggregation rounds.
+    round_configs: HashMap<AggregationRound, ObjectStoreConfig>,
+    round_stores: Mutex<HashMap<AggregationRound, Arc<dyn ObjectStore>>>,
+    /// Config of the store from which the default store reads blobs it doesn't have.
+    secondary_config: Option<ObjectStoreConfig>,
 }
 
 impl ObjectStoreFactory {
@@ -156,35 +242,132 @@ impl ObjectStoreFactory {
     /// If the GCS-backed implementation is configured, this constructor will panic if called
     /// outside the Tokio runtime.
     pub fn new(config: ObjectStoreConfig) -> Self {
+        Self::with_origin(ObjectStoreOrigin::Config(config))
+    }
+
+    fn with_origin(origin: ObjectStoreOrigin) -> Self {
         Self {
-            origin: ObjectStoreOrigin::Config(config),
+            origin,
+            round_configs: HashMap::new(),
+            round_stores: Mutex::default(),
+            secondary_config: None,
         }
     }
 
-    /// Creates an object store factory with the configuration taken from the environment.
+    /// Creates an object store factory with the configuration taken from the environment,
+    /// including the stores configured for specific aggregation rounds and the secondary store.
     pub fn from_env() -> Self {
         let config = ObjectStoreConfig::from_env();
-        Self::new(config)
+        let mut factory = Self::new(config);
+        if let Some(secondary_config) = ObjectStoreConfig::secondary_from_env() {
+            factory = factory.with_secondary_config(secondary_config);
+        }
+        for round in [
+            AggregationRound::BasicCircuits,
+            AggregationRound::LeafAggregation,
+            AggregationRound::NodeAggregation,
+            AggregationRound::Scheduler,
+        ] {
+            if let Some(round_config) = ObjectStoreConfig::round_from_env(round_env_name(round)) {
+                factory = factory.with_round_config(round, round_config);
+            }
+        }
+        factory
+    }
+
+    /// Makes [`Self::create_store_for_round()`] use a separate store (e.g., in another storage
+    /// account with its own credentials) for the given aggregation round.
+    pub fn with_round_config(mut self, round: AggregationRound, config: ObjectStoreConfig) -> Self {
+        self.round_configs.insert(round, config);
+        self
+    }
+
+    /// Makes [`Self::create_store()`] read the blobs missing from the configured store from another
+    /// store (e.g., the bucket used before a migration). Writes and removals only affect
+    /// the configured store. Doesn't affect the stores configured for aggregation rounds
+    /// or the mock store.
+    pub fn with_secondary_config(mut self, config: ObjectStoreConfig) -> Self {
+        self.secondary_config = Some(config);
+        self
     }
 
     /// Creates an object store factory with a mock in-memory store.
     /// All calls to [`Self::create_store()`] will return the same store; thus, the testing code
     /// can use [`ObjectStore`] methods for assertions.
     pub fn mock() -> Self {
-        Self {
-            origin: ObjectStoreOrigin::Mock(Arc::new(MockStore::default())),
+        Self::with_origin(ObjectStoreOrigin::Mock(Arc::new(MockStore::default())))
+    }
+
+    /// Returns the algorithm to use for content-addressed keys of the created stores
+    /// (see `put_with_content_hash()`). The mock store uses SHA-256.
+    pub fn content_hash_algorithm(&self) -> ContentHashAlgorithm {
+        match &self.origin {
+            ObjectStoreOrigin::Config(config) => config.content_hash_algorithm(),
+            ObjectStoreOrigin::Mock(_) => ContentHashAlgorithm::Sha256,
         }
     }
 
     /// Creates an [`ObjectStore`].
     pub async fn create_store(&self) -> Box<dyn ObjectStore> {
         match &self.origin {
-            ObjectStoreOrigin::Config(config) => Self::create_from_config(config).await,
+            ObjectStoreOrigin::Config(config) => {
+                let store = Self::create_from_config(config).await;
+                match &self.secondary_config {
+                    Some(secondary_config) => {
+                        let secondary = Self::create_from_config(secondary_config).await;
+                        Box::new(FallbackObjectStore::new(store, secondary))
+                    }
+                    None => store,
+                }
+            }
             ObjectStoreOrigin::Mock(store) => Box::new(Arc::clone(store)),
         }
     }
 
+    /// Creates an [`ObjectStore`] for the given aggregation round. If a separate store is configured
+    /// for the round, it's created once and shared by all subsequent calls; otherwise, this is
+    /// equivalent to [`Self::create_store()`].
+    pub async fn create_store_for_round(&self, round: AggregationRound) -> Box<dyn ObjectStore> {
+        let Some(config) = self.round_configs.get(&round) else {
+            return self.create_store().await;
+        };
+        let mut round_stores = self.round_stores.lock().await;
+        if let Some(store) = round_stores.get(&round) {
+            return Box::new(Arc::clone(store));
+        }
+        let store: Arc<dyn ObjectStore> = Self::create_from_config(config).await.into();
+        round_stores.insert(round, Arc::clone(&store));
+        Box::new(store)
+    }
+
     async fn create_from_config(config: &ObjectStoreConfig) -> Box<dyn ObjectStore> {
+        let mut store = Self::create_raw_from_config(config).await;
+        if config.verify_artifact_integrity.unwrap_or(false) {
+            store = Box::new(ChecksummedObjectStore::new(
+                store,
+                config.content_hash_algorithm(),
+            ));
+        }
+        let store = match config.retry_base_backoff() {
+            Some(base_backoff) => Box::new(RetryableObjectStore::new(
+                store,
+                config.max_retries,
+                base_backoff,
+            )),
+            None => store,
+        };
+        // Always wrapped, so that compressed blobs are loaded even if compression is disabled.
+        let store = Box::new(CompressedObjectStore::new(
+            store,
+            config.artifact_compression_level(),
+        ));
+        match config.max_cache_bytes {
+            Some(max_cache_bytes) => Box::new(CachingObjectStore::new(store, max_cache_bytes)),
+            None => store,
+        }
+    }
+
+    async fn create_raw_from_config(config: &ObjectStoreConfig) -> Box<dyn ObjectStore> {
         let gcs_credential_file_path = match config.mode {
             ObjectStoreMode::GCSWithCredentialFile => Some(config.gcs_credential_file_path.clone()),
             _ => None,
@@ -218,3 +401,12 @@ impl ObjectStoreFactory {
         }
     }
 }
+
+fn round_env_name(round: AggregationRound) -> &'static str {
+    match round {
+        AggregationRound::BasicCircuits => "basic_circuits",
+        AggregationRound::LeafAggregation => "leaf_aggregation",
+        AggregationRound::NodeAggregation => "node_aggregation",
+        AggregationRound::Scheduler => "scheduler",
+    }
+}
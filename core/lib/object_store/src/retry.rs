@@ -0,0 +1,191 @@
+//! Object store wrapper retrying transient errors, so that a flaky store doesn't fail jobs.
+
+use async_trait::async_trait;
+
+use std::{
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::raw::{Bucket, ObjectStore, ObjectStoreError};
+
+/// [`ObjectStore`] retrying failed operations with jittered exponential backoff.
+/// Only [`ObjectStoreError::Other`] errors (e.g., network errors) are retried; missing keys
+/// and (de)serialization errors are surfaced immediately.
+#[derive(Debug)]
+pub struct RetryableObjectStore {
+    inner: Box<dyn ObjectStore>,
+    max_retries: u16,
+    base_backoff: Duration,
+}
+
+impl RetryableObjectStore {
+    pub fn new(inner: Box<dyn ObjectStore>, max_retries: u16, base_backoff: Duration) -> Self {
+        Self {
+            inner,
+            max_retries,
+            base_backoff,
+        }
+    }
+
+    async fn retry<T, Fut, F>(
+        &self,
+        operation: &'static str,
+        mut f: F,
+    ) -> Result<T, ObjectStoreError>
+    where
+        Fut: Future<Output = Result<T, ObjectStoreError>>,
+        F: FnMut() -> Fut,
+    {
+        let mut retries = 0;
+        let mut backoff = self.base_backoff;
+        loop {
+            match f().await {
+                Err(ObjectStoreError::Other(err)) if retries < self.max_retries => {
+                    retries += 1;
+                    vlog::warn!(
+                        "Failed object store {operation} ({err}), retry {retries}/{}",
+                        self.max_retries
+                    );
+                    metrics::increment_counter!(
+                        "prover_fri.object_store.retries",
+                        "operation" => operation
+                    );
+                    tokio::time::sleep(with_jitter(backoff)).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Adds up to 50% of random jitter to `backoff`, so that workers failing at the same time
+/// don't retry in lockstep.
+fn with_jitter(backoff: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    backoff + backoff.mul_f64(f64::from(nanos % 1_000) / 2_000.0)
+}
+
+#[async_trait]
+impl ObjectStore for RetryableObjectStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        self.retry("get", || self.inner.get_raw(bucket, key)).await
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        self.retry("put", || self.inner.put_raw(bucket, key, value.clone()))
+            .await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.retry("remove", || self.inner.remove_raw(bucket, key))
+            .await
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        // Missing keys are skipped, so keys removed by a failed attempt don't fail the retry.
+        self.retry("remove", || self.inner.remove_many_raw(bucket, keys))
+            .await
+    }
+
+    async fn get_presigned_url(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<String, ObjectStoreError> {
+        self.retry("presign", || self.inner.get_presigned_url(bucket, key, ttl))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ObjectStoreFactory;
+
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    /// Store failing the first `failures` reads with a transient error.
+    #[derive(Debug)]
+    struct FlakyStore {
+        inner: Box<dyn ObjectStore>,
+        failures: AtomicU16,
+    }
+
+    #[async_trait]
+    impl ObjectStore for FlakyStore {
+        async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+            let failures_left = self.failures.load(Ordering::SeqCst);
+            if failures_left > 0 {
+                self.failures.store(failures_left - 1, Ordering::SeqCst);
+                return Err(ObjectStoreError::Other("service unavailable".into()));
+            }
+            self.inner.get_raw(bucket, key).await
+        }
+
+        async fn put_raw(
+            &self,
+            bucket: Bucket,
+            key: &str,
+            value: Vec<u8>,
+        ) -> Result<(), ObjectStoreError> {
+            self.inner.put_raw(bucket, key, value).await
+        }
+
+        async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+            self.inner.remove_raw(bucket, key).await
+        }
+    }
+
+    async fn retryable_store(failures: u16, max_retries: u16) -> RetryableObjectStore {
+        let inner = ObjectStoreFactory::mock().create_store().await;
+        inner
+            .put_raw(Bucket::ProverJobs, "test-key.bin", vec![1])
+            .await
+            .unwrap();
+        let flaky_store = FlakyStore {
+            inner,
+            failures: AtomicU16::new(failures),
+        };
+        RetryableObjectStore::new(Box::new(flaky_store), max_retries, Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_retried() {
+        let store = retryable_store(2, 2).await;
+        let bytes = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap();
+        assert_eq!(bytes, [1]);
+    }
+
+    #[tokio::test]
+    async fn error_is_surfaced_once_retries_are_exhausted() {
+        let store = retryable_store(3, 2).await;
+        let err = store
+            .get_raw(Bucket::ProverJobs, "test-key.bin")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ObjectStoreError::Other(_)));
+    }
+
+    #[tokio::test]
+    async fn missing_keys_are_not_retried() {
+        let store = retryable_store(0, 2).await;
+        let err = store
+            .get_raw(Bucket::ProverJobs, "missing-key.bin")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)));
+    }
+}
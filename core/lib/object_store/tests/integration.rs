@@ -1,10 +1,22 @@
 //! Integration tests for object store.
 
+use async_trait::async_trait;
+use tempdir::TempDir;
 use tokio::fs;
 
-use zksync_object_store::{Bucket, ObjectStoreFactory};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use zksync_config::configs::object_store::{ContentHashAlgorithm, ObjectStoreMode};
+use zksync_config::ObjectStoreConfig;
+use zksync_object_store::{
+    Bucket, CompressedObjectStore, ObjectStore, ObjectStoreError, ObjectStoreFactory,
+    RetryableObjectStore,
+};
 use zksync_types::{
-    proofs::{PrepareBasicCircuitsJob, StorageLogMetadata},
+    proofs::{AggregationRound, PrepareBasicCircuitsJob, StorageLogMetadata},
     L1BatchNumber,
 };
 
@@ -65,3 +77,271 @@ async fn prepare_basic_circuits_job_compatibility() {
 
     assert_job_integrity(job_tuple.1, job_tuple.0);
 }
+
+#[tokio::test]
+async fn content_hash_suffix_distinguishes_contents() {
+    let store = ObjectStoreFactory::mock().create_store().await;
+    let jobs = sample_jobs();
+    let algorithm = ContentHashAlgorithm::Sha256;
+
+    let first_key = store
+        .put_with_content_hash(L1BatchNumber(1), &jobs[0], algorithm)
+        .await
+        .unwrap();
+    let second_key = store
+        .put_with_content_hash(L1BatchNumber(1), &jobs[1], algorithm)
+        .await
+        .unwrap();
+    assert_ne!(first_key, second_key);
+    assert!(first_key.starts_with("merkel_tree_paths_1_s256-") && first_key.ends_with(".bin"));
+    // Same content must map to the same key.
+    let repeated_key = store
+        .put_with_content_hash(L1BatchNumber(1), &jobs[0], algorithm)
+        .await
+        .unwrap();
+    assert_eq!(first_key, repeated_key);
+
+    let job: PrepareBasicCircuitsJob = store.get_by_encoded_key(&second_key).await.unwrap();
+    assert_eq!(job.next_enumeration_index(), 2);
+}
+
+#[tokio::test]
+async fn content_hashed_blobs_round_trip_with_each_algorithm() {
+    let store = ObjectStoreFactory::mock().create_store().await;
+    let jobs = sample_jobs();
+    let sha256_key = store
+        .put_with_content_hash(L1BatchNumber(1), &jobs[0], ContentHashAlgorithm::Sha256)
+        .await
+        .unwrap();
+    let blake2b_key = store
+        .put_with_content_hash(L1BatchNumber(1), &jobs[0], ContentHashAlgorithm::Blake2b)
+        .await
+        .unwrap();
+    assert_ne!(sha256_key, blake2b_key);
+
+    for key in [&sha256_key, &blake2b_key] {
+        let job: PrepareBasicCircuitsJob = store.get_verified_by_content_key(key).await.unwrap();
+        assert_eq!(job.next_enumeration_index(), 1);
+    }
+
+    // Overwrite the blob with other content to check that the hash is verified.
+    let other_content = bincode::serialize(&(Vec::<StorageLogMetadata>::new(), 2_u64)).unwrap();
+    store
+        .put_raw(Bucket::WitnessInput, &blake2b_key, other_content)
+        .await
+        .unwrap();
+    let err = store
+        .get_verified_by_content_key::<PrepareBasicCircuitsJob>(&blake2b_key)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ObjectStoreError::Integrity(_)), "{err}");
+}
+
+#[tokio::test]
+async fn keys_without_content_hash_prefix_are_not_verified() {
+    let store = ObjectStoreFactory::mock().create_store().await;
+    let jobs = sample_jobs();
+    // The key ends in a block number that looks like a hash without an algorithm prefix.
+    let key = store
+        .put(L1BatchNumber(12_345_678), &jobs[0])
+        .await
+        .unwrap();
+    assert_eq!(key, "merkel_tree_paths_12345678.bin");
+
+    let err = store
+        .get_verified_by_content_key::<PrepareBasicCircuitsJob>(&key)
+        .await
+        .unwrap_err();
+    let ObjectStoreError::Integrity(err) = err else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(err.key, key);
+}
+
+#[tokio::test]
+async fn content_hash_algorithm_is_picked_from_config() {
+    let dir = TempDir::new("content-hash-algorithm").unwrap();
+    let mut config = file_backed_config(dir.path());
+    assert_eq!(
+        ObjectStoreFactory::new(config.clone()).content_hash_algorithm(),
+        ContentHashAlgorithm::Sha256
+    );
+    config.content_hash_algorithm = Some(ContentHashAlgorithm::Blake2b);
+    let factory = ObjectStoreFactory::new(config);
+    assert_eq!(
+        factory.content_hash_algorithm(),
+        ContentHashAlgorithm::Blake2b
+    );
+
+    let store = factory.create_store().await;
+    let jobs = sample_jobs();
+    let key = store
+        .put_with_content_hash(L1BatchNumber(1), &jobs[0], factory.content_hash_algorithm())
+        .await
+        .unwrap();
+    assert!(key.contains("_b2-"), "{key}");
+    assert_eq!(
+        ObjectStoreFactory::mock().content_hash_algorithm(),
+        ContentHashAlgorithm::Sha256
+    );
+}
+
+/// Store counting the removal requests it receives.
+#[derive(Debug)]
+struct RemovalCountingStore {
+    inner: Box<dyn ObjectStore>,
+    remove_calls: AtomicUsize,
+    remove_many_calls: AtomicUsize,
+}
+
+#[async_trait]
+impl ObjectStore for RemovalCountingStore {
+    async fn get_raw(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        self.inner.get_raw(bucket, key).await
+    }
+
+    async fn put_raw(
+        &self,
+        bucket: Bucket,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        self.inner.put_raw(bucket, key, value).await
+    }
+
+    async fn remove_raw(&self, bucket: Bucket, key: &str) -> Result<(), ObjectStoreError> {
+        self.remove_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.remove_raw(bucket, key).await
+    }
+
+    async fn remove_many_raw(&self, bucket: Bucket, keys: &[&str]) -> Result<(), ObjectStoreError> {
+        self.remove_many_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.remove_many_raw(bucket, keys).await
+    }
+}
+
+#[tokio::test]
+async fn many_blobs_are_removed_at_once() {
+    let counting_store = Arc::new(RemovalCountingStore {
+        inner: ObjectStoreFactory::mock().create_store().await,
+        remove_calls: AtomicUsize::new(0),
+        remove_many_calls: AtomicUsize::new(0),
+    });
+    // Wrappers must pass the batch through rather than removing the keys one by one.
+    let store = RetryableObjectStore::new(Box::new(counting_store.clone()), 3, Duration::ZERO);
+    let store = CompressedObjectStore::new(Box::new(store), None);
+    let keys = ["1.bin", "2.bin", "3.bin"];
+    for key in keys {
+        store
+            .put_raw(Bucket::ProverJobs, key, vec![1, 2, 3])
+            .await
+            .unwrap();
+    }
+
+    // Missing keys (e.g., removed by a previous cleanup run) are skipped.
+    store
+        .remove_many_raw(Bucket::ProverJobs, &["1.bin", "2.bin", "missing.bin"])
+        .await
+        .unwrap();
+    assert_eq!(counting_store.remove_many_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(counting_store.remove_calls.load(Ordering::SeqCst), 0);
+    for key in ["1.bin", "2.bin"] {
+        let err = store.get_raw(Bucket::ProverJobs, key).await.unwrap_err();
+        assert!(matches!(err, ObjectStoreError::KeyNotFound(_)), "{err}");
+    }
+    assert_eq!(
+        store.get_raw(Bucket::ProverJobs, "3.bin").await.unwrap(),
+        [1, 2, 3]
+    );
+}
+
+#[tokio::test]
+async fn rounds_use_their_configured_stores() {
+    let default_dir = TempDir::new("default-store").unwrap();
+    let scheduler_dir = TempDir::new("scheduler-store").unwrap();
+    let factory = ObjectStoreFactory::new(file_backed_config(default_dir.path()))
+        .with_round_config(
+            AggregationRound::Scheduler,
+            file_backed_config(scheduler_dir.path()),
+        );
+
+    let leaf_store = factory
+        .create_store_for_round(AggregationRound::LeafAggregation)
+        .await;
+    let scheduler_store = factory
+        .create_store_for_round(AggregationRound::Scheduler)
+        .await;
+    let bucket = Bucket::SchedulerWitnessJobsFri;
+    leaf_store.put_raw(bucket, "1.bin", vec![1]).await.unwrap();
+    scheduler_store
+        .put_raw(bucket, "1.bin", vec![3])
+        .await
+        .unwrap();
+
+    assert_eq!(leaf_store.get_raw(bucket, "1.bin").await.unwrap(), [1]);
+    assert_eq!(scheduler_store.get_raw(bucket, "1.bin").await.unwrap(), [3]);
+    let scheduler_file = scheduler_dir.path().join(format!("{bucket}/1.bin"));
+    assert_eq!(fs::read(scheduler_file).await.unwrap(), [3]);
+}
+
+#[tokio::test]
+async fn blobs_are_read_from_secondary_store_during_migration() {
+    let new_dir = TempDir::new("new-store").unwrap();
+    let old_dir = TempDir::new("old-store").unwrap();
+    let bucket = Bucket::ProofsFri;
+    let old_store = ObjectStoreFactory::new(file_backed_config(old_dir.path()))
+        .create_store()
+        .await;
+    old_store.put_raw(bucket, "1.bin", vec![1]).await.unwrap();
+
+    let store = ObjectStoreFactory::new(file_backed_config(new_dir.path()))
+        .with_secondary_config(file_backed_config(old_dir.path()))
+        .create_store()
+        .await;
+    assert_eq!(store.get_raw(bucket, "1.bin").await.unwrap(), [1]);
+
+    store.put_raw(bucket, "2.bin", vec![2]).await.unwrap();
+    let new_file = new_dir.path().join(format!("{bucket}/2.bin"));
+    assert_eq!(fs::read(new_file).await.unwrap(), [2]);
+    let err = old_store.get_raw(bucket, "2.bin").await.unwrap_err();
+    assert!(matches!(err, ObjectStoreError::KeyNotFound(_)), "{err}");
+}
+
+#[tokio::test]
+async fn presigned_urls_are_unsupported_by_file_backed_store() {
+    let dir = TempDir::new("presigned-urls").unwrap();
+    let store = ObjectStoreFactory::new(file_backed_config(dir.path()))
+        .create_store()
+        .await;
+    let err = store
+        .get_presigned_url(Bucket::ProverJobsFri, "1.bin", Duration::from_secs(60))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ObjectStoreError::Unsupported(_)), "{err}");
+}
+
+fn file_backed_config(base_path: &Path) -> ObjectStoreConfig {
+    ObjectStoreConfig {
+        bucket_base_url: String::new(),
+        mode: ObjectStoreMode::FileBacked,
+        file_backed_base_path: base_path.to_str().unwrap().to_owned(),
+        gcs_credential_file_path: String::new(),
+        max_retries: 1,
+        retry_base_backoff_ms: None,
+        content_hash_algorithm: None,
+        max_cache_bytes: None,
+        verify_artifact_integrity: None,
+        artifact_compression: None,
+        artifact_compression_level: None,
+    }
+}
+
+fn sample_jobs() -> Vec<PrepareBasicCircuitsJob> {
+    [1_u64, 2]
+        .into_iter()
+        .map(|next_enumeration_index| {
+            let job_tuple = (Vec::<StorageLogMetadata>::new(), next_enumeration_index);
+            bincode::deserialize(&bincode::serialize(&job_tuple).unwrap()).unwrap()
+        })
+        .collect()
+}
@@ -1,11 +1,58 @@
+use hyper::{Body, Client, Method, Request};
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
 use std::time::Duration;
 use tokio::task::JoinHandle;
 
+pub use metrics_exporter_prometheus::PrometheusHandle;
+
+/// Pushes the current state of metrics to the Pushgateway on demand, so that samples recorded
+/// after the last periodic push aren't lost when the process shuts down.
+#[derive(Debug)]
+pub struct PushGatewayFlusher {
+    endpoint: String,
+    handle: PrometheusHandle,
+}
+
+impl PushGatewayFlusher {
+    pub async fn flush(&self) -> Result<(), hyper::Error> {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri(&self.endpoint)
+            .body(Body::from(self.handle.render()))
+            .expect("failed to build Pushgateway request");
+        let response = Client::new().request(request).await?;
+        if !response.status().is_success() {
+            vlog::warn!(
+                "Pushgateway responded with {} to the metrics flush",
+                response.status()
+            );
+        }
+        Ok(())
+    }
+}
+
 pub fn run_prometheus_exporter(
     port: u16,
     pushgateway_config: Option<(String, Duration)>,
 ) -> JoinHandle<()> {
+    run_prometheus_exporter_with_flusher(port, pushgateway_config).0
+}
+
+/// Same as [`run_prometheus_exporter`], but also returns a flusher if metrics are pushed to the Pushgateway.
+pub fn run_prometheus_exporter_with_flusher(
+    port: u16,
+    pushgateway_config: Option<(String, Duration)>,
+) -> (JoinHandle<()>, Option<PushGatewayFlusher>) {
+    let (task, flusher, _) = run_prometheus_exporter_with_handle(port, pushgateway_config);
+    (task, flusher)
+}
+
+/// Same as [`run_prometheus_exporter_with_flusher`], but also returns a handle rendering the recorded
+/// metrics, e.g. to serve them from another HTTP server.
+pub fn run_prometheus_exporter_with_handle(
+    port: u16,
+    pushgateway_config: Option<(String, Duration)>,
+) -> (JoinHandle<()>, Option<PushGatewayFlusher>, PrometheusHandle) {
     // in seconds
     let default_latency_buckets = [0.001, 0.005, 0.025, 0.1, 0.25, 1.0, 5.0, 30.0, 120.0];
     let slow_latency_buckets = [
@@ -40,6 +87,7 @@ pub fn run_prometheus_exporter(
         0.1, 0.25, 0.5, 0.75, 1., 1.5, 2., 3., 4., 5., 10., 20., 50., 100., 1000.,
     ];
 
+    let mut push_endpoint = None;
     let builder = if let Some((pushgateway_url, push_interval)) = pushgateway_config {
         let job_id = "zksync-pushgateway";
         let namespace = std::env::var("POD_NAMESPACE").unwrap_or_else(|_| {
@@ -54,9 +102,11 @@ pub fn run_prometheus_exporter(
             "{}/metrics/job/{}/namespace/{}/pod/{}",
             pushgateway_url, job_id, namespace, pod
         );
-        PrometheusBuilder::new()
+        let builder = PrometheusBuilder::new()
             .with_push_gateway(endpoint.as_str(), push_interval)
-            .unwrap()
+            .unwrap();
+        push_endpoint = Some(endpoint);
+        builder
     } else {
         let addr = ([0, 0, 0, 0], port);
         PrometheusBuilder::new().with_http_listener(addr)
@@ -97,14 +147,73 @@ pub fn run_prometheus_exporter(
         .build()
         .expect("failed to install Prometheus recorder");
 
+    let handle = recorder.handle();
+    let flusher = push_endpoint.map(|endpoint| PushGatewayFlusher {
+        endpoint,
+        handle: handle.clone(),
+    });
     metrics::set_boxed_recorder(Box::new(recorder)).expect("failed to set metrics recorder");
 
-    tokio::spawn(async move {
+    let task = tokio::spawn(async move {
         tokio::pin!(exporter);
         loop {
             tokio::select! {
                 _ = &mut exporter => {}
             }
         }
-    })
+    });
+    (task, flusher, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server};
+    use metrics::{Key, Recorder};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn flush_pushes_metrics_recorded_before_shutdown() {
+        let pushed_bodies = Arc::new(Mutex::new(Vec::new()));
+        let make_service = make_service_fn({
+            let pushed_bodies = pushed_bodies.clone();
+            move |_| {
+                let pushed_bodies = pushed_bodies.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                        let pushed_bodies = pushed_bodies.clone();
+                        async move {
+                            let body = hyper::body::to_bytes(request.into_body()).await?;
+                            pushed_bodies
+                                .lock()
+                                .unwrap()
+                                .push(String::from_utf8(body.to_vec()).unwrap());
+                            Ok::<_, hyper::Error>(Response::new(Body::empty()))
+                        }
+                    }))
+                }
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let endpoint = format!("http://{}/metrics/job/test", server.local_addr());
+        tokio::spawn(server);
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let flusher = PushGatewayFlusher {
+            endpoint,
+            handle: recorder.handle(),
+        };
+        recorder
+            .register_counter(&Key::from_name("last_job_processed"))
+            .increment(1);
+        flusher.flush().await.unwrap();
+
+        let pushed_bodies = pushed_bodies.lock().unwrap();
+        assert_eq!(pushed_bodies.len(), 1);
+        assert!(pushed_bodies[0].contains("last_job_processed 1"));
+    }
 }
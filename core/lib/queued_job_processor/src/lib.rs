@@ -8,6 +8,8 @@ use tokio::time::sleep;
 
 use zksync_utils::panic_extractor::try_extract_panic_message;
 
+const JOBS_IN_FLIGHT_GAUGE: &str = "prover_fri.witness_generation.jobs_in_flight";
+
 #[async_trait]
 pub trait JobProcessor: Sync + Send {
     type Job: Send + 'static;
@@ -35,46 +37,28 @@ pub trait JobProcessor: Sync + Send {
         started_at: Instant,
     ) -> JoinHandle<Self::JobArtifacts>;
 
+    /// Once `stop_receiver` is signaled, no more jobs are claimed; the job being processed
+    /// is still finished and saved before returning, unless it exceeds `shutdown_timeout`.
+    ///
     /// `iterations_left`:
     /// To run indefinitely, pass `None`,
     /// To process one job, pass `Some(1)`,
     /// To process a batch, pass `Some(batch_size)`.
-    async fn run(self, stop_receiver: watch::Receiver<bool>, mut iterations_left: Option<usize>)
+    async fn run(self, stop_receiver: watch::Receiver<bool>, iterations_left: Option<usize>)
     where
         Self: Sized,
     {
-        let mut backoff: u64 = Self::POLLING_INTERVAL_MS;
-        while iterations_left.map_or(true, |i| i > 0) {
-            if *stop_receiver.borrow() {
-                vlog::warn!(
-                    "Stop signal received, shutting down {} component while waiting for a new job",
-                    Self::SERVICE_NAME
-                );
-                return;
-            }
-            if let Some((job_id, job)) = Self::get_next_job(&self).await {
-                let started_at = Instant::now();
-                backoff = Self::POLLING_INTERVAL_MS;
-                iterations_left = iterations_left.map(|i| i - 1);
-
-                vlog::debug!(
-                    "Spawning thread processing {:?} job with id {:?}",
-                    Self::SERVICE_NAME,
-                    job_id
-                );
-                let task = self.process_job(job, started_at).await;
+        let exit_when_idle = iterations_left.is_some();
+        run_loop(self, stop_receiver, iterations_left, exit_when_idle).await
+    }
 
-                self.wait_for_task(job_id, started_at, task).await
-            } else if iterations_left.is_some() {
-                vlog::info!("No more jobs to process. Server can stop now.");
-                return;
-            } else {
-                vlog::trace!("Backing off for {} ms", backoff);
-                sleep(Duration::from_millis(backoff)).await;
-                backoff = (backoff * Self::BACKOFF_MULTIPLIER).min(Self::MAX_BACKOFF_MS);
-            }
-        }
-        vlog::info!("Requested number of jobs is processed. Server can stop now.")
+    /// Processes exactly `jobs_count` jobs and returns. Unlike `run`, waits for new jobs
+    /// if there are none pending; used for benchmarks and canary deployments.
+    async fn run_for_jobs(self, stop_receiver: watch::Receiver<bool>, jobs_count: usize)
+    where
+        Self: Sized,
+    {
+        run_loop(self, stop_receiver, Some(jobs_count), false).await
     }
 
     /// Polls task handle, saving its outcome.
@@ -84,39 +68,40 @@ pub trait JobProcessor: Sync + Send {
         started_at: Instant,
         task: JoinHandle<Self::JobArtifacts>,
     ) {
-        loop {
-            vlog::trace!(
-                "Polling {} task with id {:?}. Is finished: {}",
-                Self::SERVICE_NAME,
-                job_id,
-                task.is_finished()
-            );
-            if task.is_finished() {
-                let result = task.await;
-                match result {
-                    Ok(data) => {
-                        vlog::debug!(
-                            "{} Job {:?} finished successfully",
-                            Self::SERVICE_NAME,
-                            job_id
-                        );
-                        self.save_result(job_id, started_at, data).await;
-                    }
-                    Err(error) => {
-                        let error_message = try_extract_panic_message(error);
-                        vlog::error!(
-                            "Error occurred while processing {} job {:?}: {:?}",
-                            Self::SERVICE_NAME,
-                            job_id,
-                            error_message
-                        );
-                        self.save_failure(job_id, started_at, error_message).await;
-                    }
-                }
-                break;
-            }
-            sleep(Duration::from_millis(Self::POLLING_INTERVAL_MS)).await;
-        }
+        let (job_id, result) =
+            join_task::<Self>(job_id, started_at, task, self.job_timeout(), None).await;
+        save_outcome(self, job_id, started_at, result).await
+    }
+
+    /// Max time a job may be processed for. Once it's exceeded, the job is failed and its task
+    /// is aborted; note that a blocking task that has already started runs to completion anyway,
+    /// its result is just discarded.
+    fn job_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Max time the job being processed may take to finish once `stop_receiver` is signaled.
+    /// Once it's exceeded, the job is failed with the "shutdown" error, so that it's requeued
+    /// instead of being left in progress, and its task is aborted. `None` waits for the job
+    /// indefinitely.
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether the next job should be claimed while the outcome of the previous one is being saved,
+    /// overlapping DB polling with the tail of the previous job. Jobs must then not depend
+    /// on the saved outcome of the previous job to be claimable. If the processor is stopped
+    /// in between, the claimed job is failed with the "shutdown" error.
+    fn overlap_claim_with_save(&self) -> bool {
+        false
+    }
+
+    /// Whether the next job should be claimed (and prepared by `get_next_job`) while the current one
+    /// is being processed, so that it can be started as soon as the current one is finished.
+    /// This keeps the artifacts of two jobs in memory at once. As with `overlap_claim_with_save`,
+    /// a job claimed in advance is failed with the "shutdown" error if the processor is stopped.
+    fn prefetch_next_job(&self) -> bool {
+        false
     }
 
     /// Invoked when `process_job` doesn't panic
@@ -127,3 +112,541 @@ pub trait JobProcessor: Sync + Send {
         artifacts: Self::JobArtifacts,
     );
 }
+
+async fn run_loop<P: JobProcessor>(
+    processor: P,
+    stop_receiver: watch::Receiver<bool>,
+    mut iterations_left: Option<usize>,
+    exit_when_idle: bool,
+) {
+    let mut backoff: u64 = P::POLLING_INTERVAL_MS;
+    // Job claimed while processing or saving the outcome of the previous one.
+    let mut claimed_job = None;
+    while iterations_left.map_or(true, |i| i > 0) {
+        if *stop_receiver.borrow() {
+            vlog::warn!(
+                "Stop signal received, shutting down {} component while waiting for a new job",
+                P::SERVICE_NAME
+            );
+            // Fail the job claimed in advance, so that it's requeued instead of being left in progress.
+            if let Some((job_id, _)) = claimed_job.take() {
+                processor
+                    .save_failure(job_id, Instant::now(), "shutdown".to_owned())
+                    .await;
+            }
+            return;
+        }
+        let job = match claimed_job.take() {
+            Some(job) => Some(job),
+            None => P::get_next_job(&processor).await,
+        };
+        if let Some((job_id, job)) = job {
+            let started_at = Instant::now();
+            backoff = P::POLLING_INTERVAL_MS;
+            iterations_left = iterations_left.map(|i| i - 1);
+
+            vlog::debug!(
+                "Spawning thread processing {:?} job with id {:?}",
+                P::SERVICE_NAME,
+                job_id
+            );
+            let has_iterations_left = iterations_left.map_or(true, |i| i > 0);
+            let in_flight_guard = InFlightJobGuard::new(P::SERVICE_NAME);
+            let task = processor.process_job(job, started_at).await;
+            let job_timeout = processor.job_timeout();
+            let shutdown = processor
+                .shutdown_timeout()
+                .map(|timeout| (&stop_receiver, timeout));
+            let (job_id, result) = if processor.prefetch_next_job() && has_iterations_left {
+                let (joined, next_job) = tokio::join!(
+                    join_task::<P>(job_id, started_at, task, job_timeout, shutdown),
+                    P::get_next_job(&processor)
+                );
+                claimed_job = next_job;
+                joined
+            } else {
+                join_task::<P>(job_id, started_at, task, job_timeout, shutdown).await
+            };
+            drop(in_flight_guard);
+
+            if processor.overlap_claim_with_save() && has_iterations_left && claimed_job.is_none() {
+                let (_, next_job) = tokio::join!(
+                    save_outcome(&processor, job_id, started_at, result),
+                    P::get_next_job(&processor)
+                );
+                claimed_job = next_job;
+            } else {
+                save_outcome(&processor, job_id, started_at, result).await
+            }
+        } else if exit_when_idle {
+            vlog::info!("No more jobs to process. Server can stop now.");
+            return;
+        } else {
+            vlog::trace!("Backing off for {} ms", backoff);
+            sleep(Duration::from_millis(backoff)).await;
+            backoff = (backoff * P::BACKOFF_MULTIPLIER).min(P::MAX_BACKOFF_MS);
+        }
+    }
+    vlog::info!("Requested number of jobs is processed. Server can stop now.")
+}
+
+/// Counts a job in the jobs-in-flight gauge while alive. Decrementing the gauge on drop covers
+/// every way the job can end, including `process_job` panicking and the run loop being dropped.
+struct InFlightJobGuard {
+    service_name: &'static str,
+}
+
+impl InFlightJobGuard {
+    fn new(service_name: &'static str) -> Self {
+        metrics::increment_gauge!(JOBS_IN_FLIGHT_GAUGE, 1.0, "service_name" => service_name);
+        Self { service_name }
+    }
+}
+
+impl Drop for InFlightJobGuard {
+    fn drop(&mut self) {
+        metrics::decrement_gauge!(JOBS_IN_FLIGHT_GAUGE, 1.0, "service_name" => self.service_name);
+    }
+}
+
+/// Waits for the task to finish or time out, returning the error message if it fails.
+/// If `shutdown` is set, the task is also aborted once it's still running the given timeout
+/// after the stop signal. `job_id` is returned back since it's needed to save the outcome.
+async fn join_task<P: JobProcessor + ?Sized>(
+    job_id: P::JobId,
+    started_at: Instant,
+    task: JoinHandle<P::JobArtifacts>,
+    timeout: Option<Duration>,
+    shutdown: Option<(&watch::Receiver<bool>, Duration)>,
+) -> (P::JobId, Result<P::JobArtifacts, String>) {
+    let mut stopped_at = None;
+    loop {
+        vlog::trace!(
+            "Polling {} task with id {:?}. Is finished: {}",
+            P::SERVICE_NAME,
+            job_id,
+            task.is_finished()
+        );
+        if task.is_finished() {
+            return (job_id, task.await.map_err(try_extract_panic_message));
+        }
+        if let Some(timeout) = timeout.filter(|&timeout| started_at.elapsed() >= timeout) {
+            task.abort();
+            return (job_id, Err(format!("job timed out after {:?}", timeout)));
+        }
+        if let Some((stop_receiver, shutdown_timeout)) = shutdown {
+            if *stop_receiver.borrow() {
+                let stopped_at = *stopped_at.get_or_insert_with(Instant::now);
+                if stopped_at.elapsed() >= shutdown_timeout {
+                    vlog::warn!(
+                        "{} job {:?} didn't finish within {:?} after the stop signal",
+                        P::SERVICE_NAME,
+                        job_id,
+                        shutdown_timeout
+                    );
+                    task.abort();
+                    return (job_id, Err("shutdown".to_owned()));
+                }
+            }
+        }
+        sleep(Duration::from_millis(P::POLLING_INTERVAL_MS)).await;
+    }
+}
+
+async fn save_outcome<P: JobProcessor + ?Sized>(
+    processor: &P,
+    job_id: P::JobId,
+    started_at: Instant,
+    result: Result<P::JobArtifacts, String>,
+) {
+    match result {
+        Ok(data) => {
+            vlog::debug!("{} Job {:?} finished successfully", P::SERVICE_NAME, job_id);
+            metrics::increment_counter!(
+                "prover_fri.witness_generation.jobs_total",
+                "service_name" => P::SERVICE_NAME,
+                "result" => "success",
+            );
+            processor.save_result(job_id, started_at, data).await;
+        }
+        Err(error_message) => {
+            vlog::error!(
+                "Error occurred while processing {} job {:?}: {:?}",
+                P::SERVICE_NAME,
+                job_id,
+                error_message
+            );
+            metrics::increment_counter!(
+                "prover_fri.witness_generation.jobs_total",
+                "service_name" => P::SERVICE_NAME,
+                "result" => "failure",
+            );
+            processor
+                .save_failure(job_id, started_at, error_message)
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct OverlappingProcessor {
+        claimed_jobs: AtomicUsize,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl OverlappingProcessor {
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    #[async_trait]
+    impl JobProcessor for OverlappingProcessor {
+        type Job = ();
+        type JobId = usize;
+        type JobArtifacts = ();
+
+        const POLLING_INTERVAL_MS: u64 = 10;
+        const SERVICE_NAME: &'static str = "overlapping_processor";
+
+        async fn get_next_job(&self) -> Option<(usize, ())> {
+            let job_id = self.claimed_jobs.fetch_add(1, Ordering::SeqCst);
+            self.record(format!("claim {}", job_id));
+            Some((job_id, ()))
+        }
+
+        async fn save_failure(&self, _job_id: usize, _started_at: Instant, error: String) {
+            panic!("unexpected job failure: {}", error);
+        }
+
+        async fn process_job(&self, _job: (), _started_at: Instant) -> JoinHandle<()> {
+            tokio::spawn(async {})
+        }
+
+        async fn save_result(&self, job_id: usize, _started_at: Instant, _artifacts: ()) {
+            self.record(format!("save started {}", job_id));
+            sleep(Duration::from_millis(50)).await;
+            self.record(format!("save finished {}", job_id));
+        }
+
+        fn overlap_claim_with_save(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingProcessor {
+        claimed_jobs: Arc<AtomicUsize>,
+        saved_results: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl JobProcessor for CountingProcessor {
+        type Job = ();
+        type JobId = usize;
+        type JobArtifacts = ();
+
+        const POLLING_INTERVAL_MS: u64 = 10;
+        const SERVICE_NAME: &'static str = "counting_processor";
+
+        async fn get_next_job(&self) -> Option<(usize, ())> {
+            Some((self.claimed_jobs.fetch_add(1, Ordering::SeqCst), ()))
+        }
+
+        async fn save_failure(&self, _job_id: usize, _started_at: Instant, error: String) {
+            panic!("unexpected job failure: {}", error);
+        }
+
+        async fn process_job(&self, _job: (), _started_at: Instant) -> JoinHandle<()> {
+            tokio::spawn(async {})
+        }
+
+        async fn save_result(&self, _job_id: usize, _started_at: Instant, _artifacts: ()) {
+            self.saved_results.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct TimingOutProcessor {
+        failures: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl JobProcessor for TimingOutProcessor {
+        type Job = ();
+        type JobId = usize;
+        type JobArtifacts = ();
+
+        const POLLING_INTERVAL_MS: u64 = 10;
+        const SERVICE_NAME: &'static str = "timing_out_processor";
+
+        async fn get_next_job(&self) -> Option<(usize, ())> {
+            Some((0, ()))
+        }
+
+        async fn save_failure(&self, _job_id: usize, _started_at: Instant, error: String) {
+            self.failures.lock().unwrap().push(error);
+        }
+
+        async fn process_job(&self, _job: (), _started_at: Instant) -> JoinHandle<()> {
+            tokio::spawn(sleep(Duration::from_secs(60)))
+        }
+
+        async fn save_result(&self, _job_id: usize, _started_at: Instant, _artifacts: ()) {
+            panic!("unexpected job success");
+        }
+
+        fn job_timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(30))
+        }
+    }
+
+    #[derive(Debug)]
+    struct StoppingProcessor {
+        claimed_jobs: AtomicUsize,
+        events: Arc<Mutex<Vec<String>>>,
+        stop_sender: watch::Sender<bool>,
+    }
+
+    #[async_trait]
+    impl JobProcessor for StoppingProcessor {
+        type Job = ();
+        type JobId = usize;
+        type JobArtifacts = ();
+
+        const POLLING_INTERVAL_MS: u64 = 10;
+        const SERVICE_NAME: &'static str = "stopping_processor";
+
+        async fn get_next_job(&self) -> Option<(usize, ())> {
+            let job_id = self.claimed_jobs.fetch_add(1, Ordering::SeqCst);
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("claim {}", job_id));
+            Some((job_id, ()))
+        }
+
+        async fn save_failure(&self, job_id: usize, _started_at: Instant, error: String) {
+            let event = format!("failure {}: {}", job_id, error);
+            self.events.lock().unwrap().push(event);
+        }
+
+        async fn process_job(&self, _job: (), _started_at: Instant) -> JoinHandle<()> {
+            tokio::spawn(async {})
+        }
+
+        async fn save_result(&self, job_id: usize, _started_at: Instant, _artifacts: ()) {
+            self.events.lock().unwrap().push(format!("save {}", job_id));
+            // Simulates a shutdown signal received while saving the job.
+            self.stop_sender.send(true).unwrap();
+        }
+
+        fn overlap_claim_with_save(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    struct StuckProcessor {
+        events: Arc<Mutex<Vec<String>>>,
+        stop_sender: watch::Sender<bool>,
+    }
+
+    #[async_trait]
+    impl JobProcessor for StuckProcessor {
+        type Job = ();
+        type JobId = usize;
+        type JobArtifacts = ();
+
+        const POLLING_INTERVAL_MS: u64 = 10;
+        const SERVICE_NAME: &'static str = "stuck_processor";
+
+        async fn get_next_job(&self) -> Option<(usize, ())> {
+            self.events.lock().unwrap().push("claim 0".to_owned());
+            Some((0, ()))
+        }
+
+        async fn save_failure(&self, job_id: usize, _started_at: Instant, error: String) {
+            let event = format!("failure {}: {}", job_id, error);
+            self.events.lock().unwrap().push(event);
+        }
+
+        async fn process_job(&self, _job: (), _started_at: Instant) -> JoinHandle<()> {
+            // Simulates a shutdown signal received while processing the job.
+            self.stop_sender.send(true).unwrap();
+            tokio::spawn(sleep(Duration::from_secs(60)))
+        }
+
+        async fn save_result(&self, _job_id: usize, _started_at: Instant, _artifacts: ()) {
+            panic!("unexpected job success");
+        }
+
+        fn shutdown_timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(30))
+        }
+    }
+
+    #[derive(Debug)]
+    struct PrefetchingProcessor {
+        claimed_jobs: AtomicUsize,
+        events: Arc<Mutex<Vec<String>>>,
+        stop_sender: Option<watch::Sender<bool>>,
+    }
+
+    impl PrefetchingProcessor {
+        fn new(stop_sender: Option<watch::Sender<bool>>) -> Self {
+            Self {
+                claimed_jobs: AtomicUsize::new(0),
+                events: Arc::default(),
+                stop_sender,
+            }
+        }
+
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    #[async_trait]
+    impl JobProcessor for PrefetchingProcessor {
+        type Job = ();
+        type JobId = usize;
+        type JobArtifacts = ();
+
+        const POLLING_INTERVAL_MS: u64 = 10;
+        const SERVICE_NAME: &'static str = "prefetching_processor";
+
+        async fn get_next_job(&self) -> Option<(usize, ())> {
+            let job_id = self.claimed_jobs.fetch_add(1, Ordering::SeqCst);
+            self.record(format!("claim {}", job_id));
+            Some((job_id, ()))
+        }
+
+        async fn save_failure(&self, job_id: usize, _started_at: Instant, error: String) {
+            self.record(format!("failure {}: {}", job_id, error));
+        }
+
+        async fn process_job(&self, _job: (), _started_at: Instant) -> JoinHandle<()> {
+            tokio::spawn(sleep(Duration::from_millis(50)))
+        }
+
+        async fn save_result(&self, job_id: usize, _started_at: Instant, _artifacts: ()) {
+            self.record(format!("save {}", job_id));
+            if let Some(stop_sender) = &self.stop_sender {
+                stop_sender.send(true).unwrap();
+            }
+        }
+
+        fn prefetch_next_job(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn next_job_is_claimed_while_processing_previous_one() {
+        let processor = PrefetchingProcessor::new(None);
+        let events = processor.events.clone();
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        processor.run_for_jobs(stop_receiver, 2).await;
+
+        let events = events.lock().unwrap();
+        assert_eq!(*events, ["claim 0", "claim 1", "save 0", "save 1"]);
+    }
+
+    #[tokio::test]
+    async fn job_prefetched_before_shutdown_is_failed() {
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let processor = PrefetchingProcessor::new(Some(stop_sender));
+        let events = processor.events.clone();
+        processor.run_for_jobs(stop_receiver, 5).await;
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            ["claim 0", "claim 1", "save 0", "failure 1: shutdown"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_for_jobs_stops_after_requested_number_of_jobs() {
+        let processor = CountingProcessor::default();
+        let claimed_jobs = processor.claimed_jobs.clone();
+        let saved_results = processor.saved_results.clone();
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        processor.run_for_jobs(stop_receiver, 3).await;
+
+        assert_eq!(claimed_jobs.load(Ordering::SeqCst), 3);
+        assert_eq!(saved_results.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn next_job_is_claimed_while_saving_previous_one() {
+        let processor = OverlappingProcessor::default();
+        let events = processor.events.clone();
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        processor.run_for_jobs(stop_receiver, 2).await;
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [
+                "claim 0",
+                "save started 0",
+                "claim 1",
+                "save finished 0",
+                "save started 1",
+                "save finished 1"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn job_exceeding_timeout_is_failed() {
+        let processor = TimingOutProcessor::default();
+        let failures = processor.failures.clone();
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        processor.run(stop_receiver, Some(1)).await;
+
+        let failures = failures.lock().unwrap();
+        assert_eq!(*failures, ["job timed out after 30ms"]);
+    }
+
+    #[tokio::test]
+    async fn job_claimed_before_shutdown_is_failed() {
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let processor = StoppingProcessor {
+            claimed_jobs: AtomicUsize::new(0),
+            events: Arc::default(),
+            stop_sender,
+        };
+        let events = processor.events.clone();
+        processor.run_for_jobs(stop_receiver, 5).await;
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            ["claim 0", "save 0", "claim 1", "failure 1: shutdown"]
+        );
+    }
+
+    #[tokio::test]
+    async fn job_exceeding_shutdown_timeout_is_failed() {
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let processor = StuckProcessor {
+            events: Arc::default(),
+            stop_sender,
+        };
+        let events = processor.events.clone();
+        processor.run_for_jobs(stop_receiver, 5).await;
+
+        let events = events.lock().unwrap();
+        assert_eq!(*events, ["claim 0", "failure 0: shutdown"]);
+    }
+}
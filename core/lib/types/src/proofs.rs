@@ -1,5 +1,5 @@
 use std::convert::{TryFrom, TryInto};
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::ops::Add;
 use std::str::FromStr;
 
@@ -87,6 +87,13 @@ impl From<u8> for AggregationRound {
 }
 
 impl AggregationRound {
+    pub const ALL: [AggregationRound; 4] = [
+        AggregationRound::BasicCircuits,
+        AggregationRound::LeafAggregation,
+        AggregationRound::NodeAggregation,
+        AggregationRound::Scheduler,
+    ];
+
     pub fn next(&self) -> Option<AggregationRound> {
         match self {
             AggregationRound::BasicCircuits => Some(AggregationRound::LeafAggregation),
@@ -102,12 +109,13 @@ impl FromStr for AggregationRound {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "basic_circuits" => Ok(AggregationRound::BasicCircuits),
-            "leaf_aggregation" => Ok(AggregationRound::LeafAggregation),
-            "node_aggregation" => Ok(AggregationRound::NodeAggregation),
+            "basic_circuits" | "basic" => Ok(AggregationRound::BasicCircuits),
+            "leaf_aggregation" | "leaf" => Ok(AggregationRound::LeafAggregation),
+            "node_aggregation" | "node" => Ok(AggregationRound::NodeAggregation),
             "scheduler" => Ok(AggregationRound::Scheduler),
             other => Err(format!(
-                "{} is not a valid round name for witness generation",
+                "{} is not a valid round name for witness generation, expected one of \
+                 basic, leaf, node, scheduler",
                 other
             )),
         }
@@ -266,12 +274,67 @@ pub struct FriProverJobMetadata {
     pub is_node_final_proof: bool,
 }
 
+/// Id of a base layer circuit, i.e. of a circuit proving a part of the batch execution.
+/// Kept distinct from [`RecursiveLayerCircuitId`], since the two id spaces overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BaseLayerCircuitId(pub u8);
+
+/// Id of a recursion layer circuit (leaf, node or scheduler), i.e. of a circuit aggregating proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RecursiveLayerCircuitId(pub u8);
+
+macro_rules! impl_circuit_id_conversions {
+    ($name:ident) => {
+        impl From<u8> for $name {
+            fn from(id: u8) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, formatter)
+            }
+        }
+    };
+}
+
+impl_circuit_id_conversions!(BaseLayerCircuitId);
+impl_circuit_id_conversions!(RecursiveLayerCircuitId);
+
 #[derive(Debug, Clone)]
 pub struct LeafAggregationJobMetadata {
     pub id: u32,
     pub block_number: L1BatchNumber,
-    pub circuit_id: u8,
+    pub circuit_id: BaseLayerCircuitId,
     pub prover_job_ids_for_proofs: Vec<u32>,
+    /// Number of times the job was picked, including the current attempt.
+    pub attempts: u32,
+    /// Time the job was created at, used to measure how long it waited to be picked.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Metadata of a pending leaf aggregation job, used to snapshot and restore the job queue.
+/// Doesn't include the artifacts, which stay in the object store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeafAggregationJobRecord {
+    pub block_number: L1BatchNumber,
+    pub circuit_id: u8,
+    pub closed_form_inputs_blob_url: Option<String>,
+    pub number_of_basic_circuits: Option<usize>,
+    /// Proof availability bitmap as a string of `0` / `1` bits, if the job has one.
+    pub proof_availability_bitmap: Option<String>,
+    pub job_label: Option<String>,
+    pub status: String,
+    pub attempts: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -281,6 +344,17 @@ pub struct NodeAggregationJobMetadata {
     pub circuit_id: u8,
     pub depth: u16,
     pub prover_job_ids_for_proofs: Vec<u32>,
+    /// Key of the aggregations the job processes; not set for jobs created before it was recorded.
+    pub aggregations_url: Option<String>,
+    /// Time the job was created at, used to measure how long it waited to be picked.
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerWitnessJobMetadata {
+    pub block_number: L1BatchNumber,
+    /// Time the job was created at, used to measure how long it waited to be picked.
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug)]
@@ -432,6 +506,24 @@ pub struct StuckJobs {
 mod tests {
     use super::*;
 
+    #[test]
+    fn aggregation_round_is_parsed_from_short_and_full_names() {
+        let names_and_rounds = [
+            ("basic", AggregationRound::BasicCircuits),
+            ("basic_circuits", AggregationRound::BasicCircuits),
+            ("leaf", AggregationRound::LeafAggregation),
+            ("leaf_aggregation", AggregationRound::LeafAggregation),
+            ("node", AggregationRound::NodeAggregation),
+            ("node_aggregation", AggregationRound::NodeAggregation),
+            ("scheduler", AggregationRound::Scheduler),
+        ];
+        for (name, round) in names_and_rounds {
+            assert_eq!(name.parse::<AggregationRound>(), Ok(round));
+        }
+        let err = "compression".parse::<AggregationRound>().unwrap_err();
+        assert!(err.contains("compression"), "{err}");
+    }
+
     #[test]
     fn prepare_basic_circuits_job_roundtrip() {
         let zero_hash = [0_u8; 32];
@@ -5,6 +5,7 @@
 //! For warn and error macros we are adding file line and column to tracing variables
 //!
 //! The format of the logs in stdout can be `plain` or` json` and is set by the `MISC_LOG_FORMAT` env variable.
+//! Field values longer than `MISC_MAX_LOG_FIELD_BYTES` bytes (if set) are truncated.
 //!
 //! Full documentation for the `tracing` crate here https://docs.rs/tracing/
 //!
@@ -22,8 +23,13 @@ use sentry::protocol::Event;
 use sentry::{types::Dsn, ClientInitGuard, ClientOptions};
 use std::backtrace::Backtrace;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::fmt::format::{DefaultFields, JsonFields};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
+use crate::truncation::{TruncatingFields, TruncatingJson};
+
+mod truncation;
+
 pub use chrono as __chrono;
 pub use sentry as __sentry;
 pub use tracing as __tracing;
@@ -167,9 +173,21 @@ fn get_sampling_ratio() -> f64 {
         .unwrap_or(DEFAULT_SAMPLING_RATIO)
 }
 
+fn get_max_log_field_bytes() -> Option<usize> {
+    std::env::var("MISC_MAX_LOG_FIELD_BYTES").ok().map(|x| {
+        x.as_str().parse::<usize>().unwrap_or_else(|err| {
+            panic!(
+                "MISC_MAX_LOG_FIELD_BYTES must be a number of bytes: {}",
+                err
+            )
+        })
+    })
+}
+
 /// Initialize logging with tracing and set up log format
 pub fn init() {
     let log_format = std::env::var("MISC_LOG_FORMAT").unwrap_or_else(|_| "plain".to_string());
+    let max_log_field_bytes = get_max_log_field_bytes();
     let service_name =
         std::env::var("SERVICE_NAME").unwrap_or_else(|_| "UNKNOWN_SERVICE".to_string());
     let namespace_name =
@@ -206,15 +224,16 @@ pub fn init() {
     });
     match log_format.as_str() {
         "plain" => {
+            let fields_format = TruncatingFields::new(DefaultFields::new(), max_log_field_bytes);
             if let Some(opentelemetry) = opentelemetry {
                 tracing_subscriber::registry()
                     .with(opentelemetry)
-                    .with(fmt::Layer::default())
+                    .with(fmt::Layer::default().fmt_fields(fields_format))
                     .with(tracing_subscriber::EnvFilter::from_default_env())
                     .init();
             } else {
                 tracing_subscriber::registry()
-                    .with(fmt::Layer::default())
+                    .with(fmt::Layer::default().fmt_fields(fields_format))
                     .with(tracing_subscriber::EnvFilter::from_default_env())
                     .init();
             }
@@ -223,15 +242,25 @@ pub fn init() {
             let timer = tracing_subscriber::fmt::time::UtcTime::rfc_3339();
             // must be set before sentry hook for sentry to function
             install_pretty_panic_hook();
+            let event_format =
+                TruncatingJson::new(fmt::format().with_timer(timer).json(), max_log_field_bytes);
             if let Some(opentelemetry) = opentelemetry {
                 tracing_subscriber::registry()
                     .with(opentelemetry)
-                    .with(fmt::Layer::default().with_timer(timer).json())
+                    .with(
+                        fmt::Layer::default()
+                            .event_format(event_format)
+                            .fmt_fields(JsonFields::new()),
+                    )
                     .with(tracing_subscriber::EnvFilter::from_default_env())
                     .init();
             } else {
                 tracing_subscriber::registry()
-                    .with(fmt::Layer::default().with_timer(timer).json())
+                    .with(
+                        fmt::Layer::default()
+                            .event_format(event_format)
+                            .fmt_fields(JsonFields::new()),
+                    )
                     .with(tracing_subscriber::EnvFilter::from_default_env())
                     .init();
             }
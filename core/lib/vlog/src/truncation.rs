@@ -0,0 +1,262 @@
+//! Truncation of oversized log field values, so that a single huge value (e.g., a serialized blob
+//! in an error context) can't blow up log storage.
+
+use std::fmt;
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::{MakeVisitor, VisitFmt, VisitOutput};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Truncates `value` to at most `max_bytes` (respecting char boundaries), appending an ellipsis
+/// and the original length. Returns `None` if `value` doesn't need to be truncated.
+fn truncate(value: &str, max_bytes: usize) -> Option<String> {
+    if value.len() <= max_bytes {
+        return None;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(format!("{}... [{} bytes]", &value[..end], value.len()))
+}
+
+/// Field formatter truncating the values recorded by the wrapped formatter. Used for the `plain` log format.
+#[derive(Debug)]
+pub(crate) struct TruncatingFields<M> {
+    inner: M,
+    max_bytes: Option<usize>,
+}
+
+impl<M> TruncatingFields<M> {
+    pub fn new(inner: M, max_bytes: Option<usize>) -> Self {
+        Self { inner, max_bytes }
+    }
+}
+
+impl<'a, M: MakeVisitor<Writer<'a>>> MakeVisitor<Writer<'a>> for TruncatingFields<M> {
+    type Visitor = TruncatingVisitor<M::Visitor>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        TruncatingVisitor {
+            inner: self.inner.make_visitor(target),
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TruncatingVisitor<V> {
+    inner: V,
+    max_bytes: Option<usize>,
+}
+
+impl<V: Visit> Visit for TruncatingVisitor<V> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match self
+            .max_bytes
+            .and_then(|max_bytes| truncate(value, max_bytes))
+        {
+            Some(truncated) => self.inner.record_str(field, &truncated),
+            None => self.inner.record_str(field, value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return self.inner.record_debug(field, value),
+        };
+        match truncate(&format!("{:?}", value), max_bytes) {
+            Some(truncated) => self
+                .inner
+                .record_debug(field, &format_args!("{}", truncated)),
+            None => self.inner.record_debug(field, value),
+        }
+    }
+}
+
+impl<V: VisitOutput<fmt::Result>> VisitOutput<fmt::Result> for TruncatingVisitor<V> {
+    fn finish(self) -> fmt::Result {
+        self.inner.finish()
+    }
+}
+
+impl<V: VisitFmt> VisitFmt for TruncatingVisitor<V> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.inner.writer()
+    }
+}
+
+/// Event formatter truncating field values in the JSON lines produced by the wrapped formatter.
+/// The JSON formatter records event fields on its own rather than via the field formatter,
+/// so truncation is applied to the produced line instead.
+#[derive(Debug)]
+pub(crate) struct TruncatingJson<F> {
+    inner: F,
+    max_bytes: Option<usize>,
+}
+
+impl<F> TruncatingJson<F> {
+    pub fn new(inner: F, max_bytes: Option<usize>) -> Self {
+        Self { inner, max_bytes }
+    }
+}
+
+/// Keys of the JSON line holding the recorded event and span fields; other keys (e.g., `target`)
+/// are left intact.
+const JSON_FIELD_KEYS: [&str; 3] = ["fields", "span", "spans"];
+
+fn truncate_json_fields(json: &mut serde_json::Value, max_bytes: usize) {
+    for key in JSON_FIELD_KEYS {
+        if let Some(fields) = json.get_mut(key) {
+            truncate_json_strings(fields, max_bytes);
+        }
+    }
+}
+
+fn truncate_json_strings(value: &mut serde_json::Value, max_bytes: usize) {
+    match value {
+        serde_json::Value::String(string) => {
+            if let Some(truncated) = truncate(string, max_bytes) {
+                *string = truncated;
+            }
+        }
+        serde_json::Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| truncate_json_strings(value, max_bytes)),
+        serde_json::Value::Object(values) => values
+            .values_mut()
+            .for_each(|value| truncate_json_strings(value, max_bytes)),
+        _ => {}
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for TruncatingJson<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return self.inner.format_event(ctx, writer, event),
+        };
+        let mut line = String::new();
+        self.inner
+            .format_event(ctx, Writer::new(&mut line), event)?;
+        match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(mut json) => {
+                truncate_json_fields(&mut json, max_bytes);
+                writeln!(writer, "{}", json)
+            }
+            Err(_) => writer.write_str(&line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::fmt::format::{DefaultFields, JsonFields};
+    use tracing_subscriber::{fmt::Layer, layer::SubscriberExt};
+
+    #[derive(Debug, Clone, Default)]
+    struct BufferWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for BufferWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl BufferWriter {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn log_fields() {
+        let big = "x".repeat(100);
+        tracing::info!(big = %big, small = "ok", "message");
+    }
+
+    #[test]
+    fn oversized_fields_are_truncated_in_plain_logs() {
+        let writer = BufferWriter::default();
+        let make_writer = writer.clone();
+        let layer = Layer::default()
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .fmt_fields(TruncatingFields::new(DefaultFields::new(), Some(10)));
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), log_fields);
+
+        let logs = writer.contents();
+        assert!(logs.contains("big=xxxxxxxxxx... [100 bytes]"), "{}", logs);
+        assert!(logs.contains("small=\"ok\""), "{}", logs);
+        assert!(logs.contains("message"), "{}", logs);
+    }
+
+    #[test]
+    fn oversized_fields_are_truncated_in_json_logs() {
+        let writer = BufferWriter::default();
+        let make_writer = writer.clone();
+        let format = tracing_subscriber::fmt::format().json();
+        let layer = Layer::default()
+            .with_writer(move || make_writer.clone())
+            .event_format(TruncatingJson::new(format, Some(10)))
+            .fmt_fields(JsonFields::new());
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), log_fields);
+
+        let logs = writer.contents();
+        let json: serde_json::Value = serde_json::from_str(&logs).unwrap();
+        assert_eq!(json["fields"]["big"], "xxxxxxxxxx... [100 bytes]");
+        assert_eq!(json["fields"]["small"], "ok");
+        assert_eq!(json["fields"]["message"], "message");
+    }
+
+    #[test]
+    fn only_fields_are_truncated_in_json_logs() {
+        let writer = BufferWriter::default();
+        let make_writer = writer.clone();
+        let format = tracing_subscriber::fmt::format().json();
+        let layer = Layer::default()
+            .with_writer(move || make_writer.clone())
+            .event_format(TruncatingJson::new(format, Some(10)))
+            .fmt_fields(JsonFields::new());
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let big = "x".repeat(100);
+            let _span = tracing::info_span!("span", big = %big).entered();
+            tracing::info!(target: "truncation_test_target", "message");
+        });
+
+        let logs = writer.contents();
+        let json: serde_json::Value = serde_json::from_str(&logs).unwrap();
+        assert_eq!(json["target"], "truncation_test_target");
+        assert_eq!(json["span"]["big"], "xxxxxxxxxx... [100 bytes]");
+        assert_eq!(json["spans"][0]["big"], "xxxxxxxxxx... [100 bytes]");
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        assert_eq!(truncate("short", 10), None);
+        assert_eq!(truncate("ééé", 3).unwrap(), "é... [6 bytes]");
+    }
+}